@@ -0,0 +1,264 @@
+//! CPU affinity of processes: the CPUs they may run on, read with
+//! `sched_getaffinity` and changed from a checkbox grid with
+//! `sched_setaffinity`
+
+use std::{io, mem};
+
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style, Stylize},
+    terminal::Frame,
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, Paragraph},
+};
+
+use crate::{popup_area, priority, threads, App};
+
+/// CPUs per line of the editor
+const GRID_COLUMNS: usize = 8;
+
+/// Number of CPUs the system is configured with, online or not.
+pub fn cpu_count() -> usize {
+    // SAFETY: sysconf has no memory safety requirements
+    let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) };
+    (count.max(1) as usize).min(libc::CPU_SETSIZE as usize)
+}
+
+/// The CPUs task `tid` may run on, in ascending order.
+pub fn get(tid: u32) -> io::Result<Vec<usize>> {
+    // SAFETY: an all zero cpu_set_t is empty, and sched_getaffinity writes
+    // at most the size it is given
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    let result = unsafe {
+        libc::sched_getaffinity(
+            tid as libc::pid_t,
+            mem::size_of::<libc::cpu_set_t>(),
+            &mut set,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((0..libc::CPU_SETSIZE as usize)
+        // SAFETY: cpu is below CPU_SETSIZE
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect())
+}
+
+fn set(tid: u32, cpus: &[usize]) -> io::Result<()> {
+    // SAFETY: as in `get`, with every cpu below CPU_SETSIZE
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    for &cpu in cpus.iter().filter(|&&cpu| cpu < libc::CPU_SETSIZE as usize) {
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    let result = unsafe {
+        libc::sched_setaffinity(tid as libc::pid_t, mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(priority::refusal(
+            "processes of other users need root or CAP_SYS_NICE",
+        )),
+    }
+}
+
+/// Format CPUs as a list with ranges, such as `0-3,6`, like `taskset -c`.
+pub fn format_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<String> = vec![];
+    let mut i = 0;
+    while i < cpus.len() {
+        let start = cpus[i];
+        while i + 1 < cpus.len() && cpus[i + 1] == cpus[i] + 1 {
+            i += 1;
+        }
+        match cpus[i] {
+            end if end == start => ranges.push(start.to_string()),
+            end => ranges.push(format!("{start}-{end}")),
+        }
+        i += 1;
+    }
+    ranges.join(",")
+}
+
+/// Export an affinity as a CPU list, which keeps CSV rows flat.
+pub fn serialize<S: serde::Serializer>(
+    cpus: &Option<Vec<usize>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match cpus {
+        Some(cpus) => serializer.serialize_str(&format_list(cpus)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The CPUs the editor allows, as `sched_setaffinity` takes them.
+pub fn allowed(checked: &[bool]) -> Vec<usize> {
+    (0..checked.len()).filter(|&cpu| checked[cpu]).collect()
+}
+
+/// Restrict each target, given as a PID and whether it is a thread, to
+/// `cpus`: the whole process with `all_threads`, otherwise only the given
+/// task. Give the outcome by PID.
+pub fn apply(
+    targets: &[(u32, bool)],
+    cpus: &[usize],
+    all_threads: bool,
+) -> Vec<(u32, io::Result<()>)> {
+    targets
+        .iter()
+        .map(|&(pid, thread)| {
+            let tids = match all_threads && !thread {
+                true => threads::tids(pid),
+                false => vec![pid],
+            };
+            for tid in tids {
+                match set(tid, cpus) {
+                    Ok(()) => {}
+                    // threads may exit while we go through them
+                    Err(err) if err.raw_os_error() == Some(libc::ESRCH) && tid != pid => {}
+                    Err(err) => return (pid, Err(err)),
+                }
+            }
+            (pid, Ok(()))
+        })
+        .collect()
+}
+
+/// Describe what `apply` did in a sentence for the status line.
+pub fn summary(cpus: &[usize], results: &[(u32, io::Result<()>)]) -> String {
+    let list = format_list(cpus);
+    let failures: Vec<(u32, &io::Error)> = results
+        .iter()
+        .filter_map(|(pid, result)| Some((*pid, result.as_ref().err()?)))
+        .collect();
+    match (results, failures.as_slice()) {
+        ([(pid, _)], []) => format!("Pinned {pid} to CPUs {list}"),
+        (_, []) => format!("Pinned {} processes to CPUs {list}", results.len()),
+        ([_], [(pid, err)]) => format!("Cannot pin {pid} to CPUs {list}: {err}"),
+        (_, [(pid, err), ..]) => format!(
+            "Pinned {} of {} processes to CPUs {list}, {pid}: {err}",
+            results.len() - failures.len(),
+            results.len()
+        ),
+    }
+}
+
+/// Move the cursor of the editor by `offset` cells, `GRID_COLUMNS` being a
+/// line, staying within the CPUs.
+pub fn move_cursor(cursor: usize, offset: isize, cpus: usize) -> usize {
+    cursor
+        .saturating_add_signed(offset)
+        .min(cpus.saturating_sub(1))
+}
+
+/// Offset that moves the editor cursor one line down, up if negated.
+pub const LINE: isize = GRID_COLUMNS as isize;
+
+pub fn render_affinity(f: &mut Frame, app: &App, area: Rect) {
+    let checked = &app.affinity_cpus;
+    let mut lines: Vec<Line> = checked
+        .chunks(GRID_COLUMNS)
+        .enumerate()
+        .map(|(line, cpus)| {
+            let cells: Vec<Span> = cpus
+                .iter()
+                .enumerate()
+                .map(|(column, &on)| {
+                    let cpu = line * GRID_COLUMNS + column;
+                    let cell = Span::raw(format!("[{}] {cpu:<3}", if on { "x" } else { " " }));
+                    match cpu == app.affinity_cursor {
+                        true => cell
+                            .add_modifier(Modifier::REVERSED)
+                            .fg(app.colors.selected_style_fg),
+                        false => cell,
+                    }
+                })
+                .flat_map(|cell| [cell, Span::raw(" ")])
+                .collect();
+            Line::from(cells)
+        })
+        .collect();
+
+    let allowed = allowed(checked);
+    lines.push(Line::from(""));
+    lines.push(Line::from(match allowed.is_empty() {
+        true => String::from("No CPU allowed, check at least one"),
+        false => format!("CPUs {}", format_list(&allowed)),
+    }));
+    lines.push(Line::from(match app.affinity_targets.as_slice() {
+        [(_, true)] => String::from("Applies to this thread only"),
+        _ if app.affinity_all_threads => String::from("Applies to all threads"),
+        _ => String::from("Applies to the main thread only"),
+    }));
+
+    let title = match app.affinity_targets.as_slice() {
+        [(pid, _)] => format!("CPU affinity of process {pid}"),
+        targets => format!("CPU affinity of {} processes", targets.len()),
+    };
+    let area = popup_area(area, GRID_COLUMNS as u16 * 9 + 2, lines.len() as u16 + 2);
+    let editor = Paragraph::new(lines)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title(title)
+                .title_bottom(
+                    "(Space) toggle | (a) all | (Tab) threads | (Enter) apply | (Esc) cancel",
+                ),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_widget(editor, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_cpu_lists_with_ranges() {
+        assert_eq!(format_list(&[]), "");
+        assert_eq!(format_list(&[3]), "3");
+        assert_eq!(format_list(&[0, 1, 2, 3]), "0-3");
+        assert_eq!(format_list(&[0, 2, 4]), "0,2,4");
+        assert_eq!(format_list(&[0, 1, 3, 4, 5, 7, 10, 11]), "0-1,3-5,7,10-11");
+    }
+
+    #[test]
+    fn lists_checked_cpus() {
+        assert_eq!(allowed(&[true, false, true, true]), [0, 2, 3]);
+        assert_eq!(allowed(&[false, false]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn cursor_stays_on_the_cpus() {
+        assert_eq!(move_cursor(0, -1, 4), 0);
+        assert_eq!(move_cursor(2, 1, 4), 3);
+        assert_eq!(move_cursor(3, 1, 4), 3);
+        assert_eq!(move_cursor(1, LINE, 12), 9);
+        assert_eq!(move_cursor(9, LINE, 12), 11);
+        assert_eq!(move_cursor(9, -LINE, 12), 1);
+        assert_eq!(move_cursor(0, 1, 0), 0);
+    }
+
+    #[test]
+    fn summarizes_outcomes() {
+        let failed = || Err(io::Error::from_raw_os_error(libc::EPERM));
+        assert_eq!(summary(&[0, 1], &[(5, Ok(()))]), "Pinned 5 to CPUs 0-1");
+        assert_eq!(
+            summary(&[2], &[(5, Ok(())), (6, Ok(()))]),
+            "Pinned 2 processes to CPUs 2"
+        );
+        assert!(summary(&[2], &[(5, failed())]).starts_with("Cannot pin 5 to CPUs 2: "));
+        assert!(summary(&[2], &[(5, Ok(())), (6, failed())])
+            .starts_with("Pinned 1 of 2 processes to CPUs 2, 6: "));
+    }
+
+    #[test]
+    fn reads_the_own_affinity() {
+        let cpus = get(std::process::id()).unwrap();
+        assert!(!cpus.is_empty());
+        assert!(cpus.iter().all(|&cpu| cpu < cpu_count()));
+    }
+}
@@ -0,0 +1,56 @@
+//! Non-interactive output of the process table
+
+use std::{
+    io::{self, Write},
+    thread,
+};
+
+use tabled::{builder::Builder, settings::Style};
+
+use crate::{
+    export::{self, Format},
+    App,
+};
+
+/// Print the filtered and sorted table `iterations` times in `format`,
+/// refreshing the processes in between.
+pub fn run(app: &mut App, iterations: usize, format: Format) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    for iteration in 0..iterations {
+        if iteration > 0 {
+            thread::sleep(app.refresh_interval);
+            app.refresh();
+            if format == Format::Table {
+                writeln!(stdout)?;
+            }
+        }
+
+        let written = if format == Format::Table {
+            let mut builder = Builder::default();
+            builder.push_record(app.columns.iter().map(|column| column.label()));
+            for n in app.visible_rows() {
+                builder.push_record(
+                    app.columns
+                        .iter()
+                        .map(|column| app.items[n][column.index()].as_str()),
+                );
+            }
+            let mut table = builder.build();
+            table.with(Style::blank());
+            writeln!(stdout, "{table}")
+        } else {
+            let rows = app.visible_rows();
+            let processes: Vec<_> = rows.iter().map(|&n| &app.rows[n]).collect();
+            export::write_records(&mut stdout, format, &processes, iteration == 0)
+        };
+
+        match written.and_then(|_| stdout.flush()) {
+            // the reader went away, e.g. piped into `head`
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+    }
+
+    Ok(())
+}
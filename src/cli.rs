@@ -0,0 +1,327 @@
+//! Command line options
+
+use std::{path::PathBuf, time::Duration};
+
+use ratatui::crossterm::event::KeyCode;
+
+use users::{get_user_by_name, get_user_by_uid};
+
+use crate::{
+    column::Column,
+    export::Format,
+    filter::{self, Field, Filter},
+    keys::Action,
+    theme::Theme,
+};
+
+pub const USAGE: &str = "\
+Usage: rustytop [OPTIONS]
+
+Options:
+  -u, --user <USER>        only show processes owned by USER, a name or a UID
+  -N, --name <PATTERN>     only show processes whose name matches PATTERN
+  -P, --path <PATTERN>     only show processes whose executable path matches PATTERN
+  -p, --pid <PID,...>      only show the given PIDs, the option can be repeated
+  -f, --filter <QUERY>     only show processes matching QUERY, e.g. 'user=root and cpu>1'
+  -s, --sort <COLUMN>      sort by a column, e.g. pid, name, user, cpu, rss or read_rate
+  -r, --reverse            reverse the sort order
+  -d, --delay <SECONDS>    time between refreshes, e.g. 1.5
+  -c, --color <THEME>      color theme: blue, emerald, indigo, red, high-contrast,
+                           mono or one defined in the config file; without
+                           it NO_COLOR picks mono over the configured theme
+  -t, --tree               show processes as a tree
+      --no-tree            show processes as a flat list
+  -H, --threads            list every thread along with the processes
+  -b, --batch              print the process table to stdout instead of starting the TUI
+  -n, --iterations <N>     number of tables to print in batch mode (implies --batch)
+  -o, --output <FORMAT>    batch output format: table, json, csv or ndjson (implies --batch)
+  -C, --config <FILE>      read and save preferences in FILE instead of
+                           $XDG_CONFIG_HOME/rustytop/config.toml
+  -h, --help               print this help
+
+PATTERN is text, matched ignoring case if it is all lowercase. For a regex, use
+a filter such as -f 'name~/^kworker/'.";
+
+#[derive(Default)]
+pub struct Options {
+    pub batch: bool,
+    pub iterations: Option<usize>,
+    pub delay: Option<Duration>,
+    pub sort: Option<Column>,
+    pub reverse: bool,
+    /// Initial filters, each a query and the column its bare patterns match
+    pub filters: Vec<(String, Option<Field>)>,
+    pub tree: Option<bool>,
+    pub threads: Option<bool>,
+    /// Name of the theme, checked once the config file has been read
+    pub palette: Option<String>,
+    pub output: Option<Format>,
+    pub config: Option<PathBuf>,
+    pub help: bool,
+
+    // the remaining settings only come from the config file
+    /// Sort direction, the natural one of `sort` if unset
+    pub descending: Option<bool>,
+    /// Visible columns in display order, all of them if unset
+    pub columns: Option<Vec<Column>>,
+    pub widths: Vec<(Column, u16)>,
+    pub keys: Vec<(Action, Vec<KeyCode>)>,
+    /// Themes defined in the config file
+    pub themes: Vec<Theme>,
+}
+
+/// Parse the command line, `args` not including the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    let mut pids: Vec<u32> = vec![];
+
+    while let Some(arg) = args.next() {
+        // accept both `--sort cpu` and `--sort=cpu`
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{name} needs a value"))
+        };
+
+        match flag.as_str() {
+            "-u" | "--user" => {
+                let text = value(&flag)?;
+                options
+                    .filters
+                    .push((format!("user={}", filter::quote(&user_name(&text)?)), None));
+            }
+            "-N" | "--name" | "-P" | "--path" => {
+                let text = value(&flag)?;
+                let field = match flag.as_str() {
+                    "-N" | "--name" => Field::Name,
+                    _ => Field::Path,
+                };
+                // quoted, so slashes, spaces and operators are matched literally
+                options.filters.push((filter::quote(&text), Some(field)));
+            }
+            "-p" | "--pid" => {
+                let text = value(&flag)?;
+                for pid in text.split(',').map(str::trim).filter(|pid| !pid.is_empty()) {
+                    pids.push(pid.parse::<u32>().map_err(|_| {
+                        format!("{flag} expects PIDs separated by commas, got '{pid}'")
+                    })?);
+                }
+            }
+            "-f" | "--filter" => {
+                let text = value(&flag)?;
+                Filter::parse(&text, None).map_err(|err| format!("invalid filter: {err}"))?;
+                options.filters.push((text, None));
+            }
+            "-s" | "--sort" => {
+                let text = value(&flag)?;
+                options.sort = Some(Column::from_name(&text).ok_or_else(|| {
+                    format!("unknown sort column '{text}', expected {}", Column::names())
+                })?);
+            }
+            "-r" | "--reverse" => options.reverse = true,
+            "-d" | "--delay" => {
+                let text = value(&flag)?;
+                let seconds = text
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                    .ok_or_else(|| format!("{flag} expects a number of seconds, got '{text}'"))?;
+                options.delay = Some(Duration::from_secs_f64(seconds));
+            }
+            "-c" | "--color" => {
+                options.palette = Some(value(&flag)?);
+            }
+            "-t" | "--tree" | "--no-tree" => {
+                let tree = flag != "--no-tree";
+                if options.tree == Some(!tree) {
+                    return Err(String::from("--tree and --no-tree cannot be used together"));
+                }
+                options.tree = Some(tree);
+            }
+            "-H" | "--threads" => options.threads = Some(true),
+            "-b" | "--batch" => options.batch = true,
+            "-n" | "--iterations" => {
+                let text = value(&flag)?;
+                let iterations = text
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("{flag} expects a positive number, got '{text}'"))?;
+                options.iterations = Some(iterations);
+                options.batch = true;
+            }
+            "-o" | "--output" => {
+                let text = value(&flag)?;
+                options.output = Some(Format::from_name(&text).ok_or_else(|| {
+                    format!("unknown output format '{text}', expected table, json, csv or ndjson")
+                })?);
+                options.batch = true;
+            }
+            "-C" | "--config" => options.config = Some(PathBuf::from(value(&flag)?)),
+            "-h" | "--help" => options.help = true,
+            _ if !arg.starts_with('-') => {
+                return Err(format!(
+                    "unexpected argument '{arg}', options start with '-'"
+                ))
+            }
+            _ => return Err(format!("unknown option '{arg}'")),
+        }
+    }
+
+    if !pids.is_empty() {
+        let query = pids
+            .iter()
+            .map(|pid| format!("pid={pid}"))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        options.filters.push((query, None));
+    }
+
+    Ok(options)
+}
+
+/// The login name of `user`, given as a name or a numeric UID. UIDs without a
+/// user are kept as numbers, since that is how the table shows them.
+fn user_name(user: &str) -> Result<String, String> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(get_user_by_uid(uid)
+            .map(|found| found.name().to_string_lossy().into_owned())
+            .unwrap_or_else(|| uid.to_string()));
+    }
+    get_user_by_name(user)
+        .map(|found| found.name().to_string_lossy().into_owned())
+        .ok_or_else(|| format!("unknown user '{user}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::ProcessMap;
+
+    fn parse_args(args: &[&str]) -> Result<Options, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    /// The filters the arguments start with, each as a parsed query
+    fn filters(args: &[&str]) -> Vec<Filter> {
+        parse_args(args)
+            .unwrap()
+            .filters
+            .iter()
+            .map(|(query, field)| Filter::parse(query, *field).unwrap())
+            .collect()
+    }
+
+    fn passes(filters: &[Filter], name: &str, path: &str) -> bool {
+        let process = ProcessMap {
+            pid: 7,
+            name: name.to_string(),
+            path: path.to_string(),
+            user: String::from("root"),
+            ..ProcessMap::default()
+        };
+        let cells = [name, path, "root"];
+        filters
+            .iter()
+            .all(|filter| filter.matches(&process, &cells))
+    }
+
+    #[test]
+    fn patterns_are_matched_literally() {
+        let path = filters(&["-P", "/usr/bin/"]);
+        assert_eq!(path[0].to_string(), r#"path~"/usr/bin/""#);
+        assert!(passes(&path, "ls", "/usr/bin/ls"));
+        assert!(!passes(&path, "ls", "/usr/local/bin/ls"));
+
+        let name = filters(&["--name", r#"say "hi" \o/ or not"#]);
+        assert!(passes(&name, r#"say "hi" \o/ or not"#, "/bin/say"));
+        assert!(!passes(&name, "say", "/bin/say"));
+
+        let name = filters(&["-N", "/w+/"]);
+        assert!(passes(&name, "a /w+/ b", "/bin/a"));
+        assert!(!passes(&name, "www", "/bin/www"));
+
+        // lowercase text ignores case
+        assert!(passes(&filters(&["-N", "émile"]), "ÉMILE", "/x"));
+    }
+
+    #[test]
+    fn users_and_pids_become_filters() {
+        let options = parse_args(&["-u", "0", "-p", "1, 2", "--pid=3"]).unwrap();
+        let queries: Vec<&str> = options
+            .filters
+            .iter()
+            .map(|(query, _)| query.as_str())
+            .collect();
+        assert_eq!(queries, [r#"user="root""#, "pid=1 or pid=2 or pid=3"]);
+    }
+
+    #[test]
+    fn reads_values_and_flags() {
+        let options = parse_args(&[
+            "--sort=cpu",
+            "-r",
+            "-d",
+            "1.5",
+            "-t",
+            "-H",
+            "-n",
+            "3",
+            "-c",
+            "red",
+        ])
+        .unwrap();
+        assert!(options.sort == Some(Column::Cpu));
+        assert!(options.reverse);
+        assert_eq!(options.delay, Some(Duration::from_millis(1500)));
+        assert_eq!(options.tree, Some(true));
+        assert_eq!(options.threads, Some(true));
+        assert_eq!(options.iterations, Some(3));
+        assert!(options.batch);
+        assert_eq!(options.palette.as_deref(), Some("red"));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let error = |args: &[&str]| match parse_args(args) {
+            Ok(_) => panic!("{args:?} parsed"),
+            Err(err) => err,
+        };
+        assert_eq!(error(&["-s"]), "-s needs a value");
+        assert!(error(&["-s", "colour"]).starts_with("unknown sort column 'colour', expected"));
+        assert_eq!(
+            error(&["-d", "0"]),
+            "-d expects a number of seconds, got '0'"
+        );
+        assert_eq!(
+            error(&["-d", "inf"]),
+            "-d expects a number of seconds, got 'inf'"
+        );
+        assert_eq!(error(&["-n", "0"]), "-n expects a positive number, got '0'");
+        assert_eq!(
+            error(&["-p", "1,x"]),
+            "-p expects PIDs separated by commas, got 'x'"
+        );
+        assert_eq!(
+            error(&["-f", "cpu>"]),
+            "invalid filter: column 5: expected a value after 'cpu>'"
+        );
+        assert_eq!(
+            error(&["--tree", "--no-tree"]),
+            "--tree and --no-tree cannot be used together"
+        );
+        assert_eq!(error(&["--bogus"]), "unknown option '--bogus'");
+        assert_eq!(
+            error(&["top"]),
+            "unexpected argument 'top', options start with '-'"
+        );
+    }
+}
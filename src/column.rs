@@ -0,0 +1,424 @@
+//! The registry of table columns: what each one shows, its header, its width
+//! and how it sorts
+
+use std::cmp::Ordering;
+
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    terminal::Frame,
+    text::Line,
+    widgets::{Block, BorderType, Clear, List},
+};
+
+use crate::{
+    affinity, files, popup_area,
+    process::{self, ProcessMap},
+    App,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Pid,
+    Ppid,
+    Name,
+    Path,
+    User,
+    Cpu,
+    Rss,
+    Virt,
+    Mem,
+    State,
+    Threads,
+    Processor,
+    VoluntarySwitches,
+    InvoluntarySwitches,
+    Fds,
+    Pss,
+    Uss,
+    Nice,
+    Policy,
+    IoPriority,
+    Affinity,
+    ReadRate,
+    WriteRate,
+    ReadBytes,
+    WriteBytes,
+    CancelledWriteBytes,
+    ReadChars,
+    WriteChars,
+    ReadSyscalls,
+    WriteSyscalls,
+}
+
+impl Column {
+    /// Every column, in the order the column editor lists hidden ones
+    pub const ALL: [Column; 30] = [
+        Column::Pid,
+        Column::Ppid,
+        Column::Name,
+        Column::Path,
+        Column::User,
+        Column::Cpu,
+        Column::Rss,
+        Column::Virt,
+        Column::Mem,
+        Column::State,
+        Column::Threads,
+        Column::Processor,
+        Column::VoluntarySwitches,
+        Column::InvoluntarySwitches,
+        Column::Fds,
+        Column::Pss,
+        Column::Uss,
+        Column::Nice,
+        Column::Policy,
+        Column::IoPriority,
+        Column::Affinity,
+        Column::ReadRate,
+        Column::WriteRate,
+        Column::ReadBytes,
+        Column::WriteBytes,
+        Column::CancelledWriteBytes,
+        Column::ReadChars,
+        Column::WriteChars,
+        Column::ReadSyscalls,
+        Column::WriteSyscalls,
+    ];
+
+    /// Columns shown when the config file does not list any
+    pub const DEFAULT: [Column; 8] = [
+        Column::Pid,
+        Column::Name,
+        Column::Path,
+        Column::User,
+        Column::Cpu,
+        Column::Rss,
+        Column::Virt,
+        Column::Mem,
+    ];
+
+    /// Look a column up by its command line name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let column = match name.to_lowercase().as_str() {
+            "pid" => Column::Pid,
+            "ppid" => Column::Ppid,
+            "name" => Column::Name,
+            "path" | "file" => Column::Path,
+            "user" => Column::User,
+            "cpu" => Column::Cpu,
+            "rss" => Column::Rss,
+            "virt" => Column::Virt,
+            "mem" => Column::Mem,
+            "state" => Column::State,
+            "threads" => Column::Threads,
+            "processor" => Column::Processor,
+            "vcsw" => Column::VoluntarySwitches,
+            "icsw" => Column::InvoluntarySwitches,
+            "fds" => Column::Fds,
+            "pss" => Column::Pss,
+            "uss" => Column::Uss,
+            "nice" => Column::Nice,
+            "sched" => Column::Policy,
+            "io" => Column::IoPriority,
+            "affinity" => Column::Affinity,
+            "read_rate" => Column::ReadRate,
+            "write_rate" => Column::WriteRate,
+            "read_bytes" => Column::ReadBytes,
+            "write_bytes" => Column::WriteBytes,
+            "cancelled_write_bytes" => Column::CancelledWriteBytes,
+            "rchar" => Column::ReadChars,
+            "wchar" => Column::WriteChars,
+            "syscr" => Column::ReadSyscalls,
+            "syscw" => Column::WriteSyscalls,
+            _ => return None,
+        };
+        Some(column)
+    }
+
+    /// The name `from_name` accepts for the column, as used in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::Pid => "pid",
+            Column::Ppid => "ppid",
+            Column::Name => "name",
+            Column::Path => "path",
+            Column::User => "user",
+            Column::Cpu => "cpu",
+            Column::Rss => "rss",
+            Column::Virt => "virt",
+            Column::Mem => "mem",
+            Column::State => "state",
+            Column::Threads => "threads",
+            Column::Processor => "processor",
+            Column::VoluntarySwitches => "vcsw",
+            Column::InvoluntarySwitches => "icsw",
+            Column::Fds => "fds",
+            Column::Pss => "pss",
+            Column::Uss => "uss",
+            Column::Nice => "nice",
+            Column::Policy => "sched",
+            Column::IoPriority => "io",
+            Column::Affinity => "affinity",
+            Column::ReadRate => "read_rate",
+            Column::WriteRate => "write_rate",
+            Column::ReadBytes => "read_bytes",
+            Column::WriteBytes => "write_bytes",
+            Column::CancelledWriteBytes => "cancelled_write_bytes",
+            Column::ReadChars => "rchar",
+            Column::WriteChars => "wchar",
+            Column::ReadSyscalls => "syscr",
+            Column::WriteSyscalls => "syscw",
+        }
+    }
+
+    /// The names of all columns, for error messages.
+    pub fn names() -> String {
+        let names: Vec<_> = Self::ALL.iter().map(|column| column.name()).collect();
+        names.join(", ")
+    }
+
+    /// Header of the column in the table.
+    pub fn label(self) -> &'static str {
+        match self {
+            Column::Pid => "PID",
+            Column::Ppid => "PPID",
+            Column::Name => "NAME",
+            Column::Path => "PATH",
+            Column::User => "USER",
+            Column::Cpu => "CPU%",
+            Column::Rss => "RSS",
+            Column::Virt => "VIRT",
+            Column::Mem => "MEM%",
+            Column::State => "S",
+            Column::Threads => "THR",
+            Column::Processor => "CPU#",
+            Column::VoluntarySwitches => "VCSW",
+            Column::InvoluntarySwitches => "ICSW",
+            Column::Fds => "FDS",
+            Column::Pss => "PSS",
+            Column::Uss => "USS",
+            Column::Nice => "NI",
+            Column::Policy => "SCHED",
+            Column::IoPriority => "IO",
+            Column::Affinity => "AFFINITY",
+            Column::ReadRate => "DISK-R/s",
+            Column::WriteRate => "DISK-W/s",
+            Column::ReadBytes => "DISK-R",
+            Column::WriteBytes => "DISK-W",
+            Column::CancelledWriteBytes => "CANCEL-W",
+            Column::ReadChars => "RCHAR",
+            Column::WriteChars => "WCHAR",
+            Column::ReadSyscalls => "SYSCR",
+            Column::WriteSyscalls => "SYSCW",
+        }
+    }
+
+    /// Position of the column in `ALL`, and of its cell in a row.
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|&c| c == self).unwrap_or(0)
+    }
+
+    /// Resource columns are most useful with the biggest consumers on top.
+    pub fn default_descending(self) -> bool {
+        matches!(
+            self,
+            Column::Cpu
+                | Column::Rss
+                | Column::Virt
+                | Column::Mem
+                | Column::Threads
+                | Column::VoluntarySwitches
+                | Column::InvoluntarySwitches
+                | Column::Fds
+                | Column::Pss
+                | Column::Uss
+                | Column::ReadRate
+                | Column::WriteRate
+                | Column::ReadBytes
+                | Column::WriteBytes
+                | Column::CancelledWriteBytes
+                | Column::ReadChars
+                | Column::WriteChars
+                | Column::ReadSyscalls
+                | Column::WriteSyscalls
+        )
+    }
+
+    /// Narrowest width the column is squeezed to when the terminal is too
+    /// small for every column, room for the header and sort indicator.
+    pub fn min_width(self) -> u16 {
+        match self {
+            Column::Name | Column::Path => 10,
+            _ => self.label().len() as u16 + 1,
+        }
+    }
+
+    /// The text of the column for `process`.
+    pub fn cell(self, process: &ProcessMap) -> String {
+        let bytes = |bytes: Option<u64>| bytes.map(process::format_bytes).unwrap_or_default();
+        let count = |count: Option<u64>| count.map(|count| count.to_string()).unwrap_or_default();
+        match self {
+            Column::Pid => process.pid.to_string(),
+            Column::Ppid => process
+                .parent
+                .map(|parent| parent.to_string())
+                .unwrap_or_default(),
+            Column::Name => process.name.clone(),
+            Column::Path => process.path.clone(),
+            Column::User => process.user.clone(),
+            Column::Cpu => format!("{:.1}", process.cpu),
+            Column::Rss => process::format_bytes(process.memory),
+            Column::Virt => process::format_bytes(process.virtual_memory),
+            Column::Mem => format!("{:.1}", process.memory_percent),
+            Column::State => process.state.clone(),
+            Column::Threads => process.threads.to_string(),
+            Column::Processor => process
+                .processor
+                .map(|cpu| cpu.to_string())
+                .unwrap_or_default(),
+            Column::VoluntarySwitches => process.voluntary_switches.to_string(),
+            Column::InvoluntarySwitches => process.involuntary_switches.to_string(),
+            // flag processes about to run out of descriptors
+            Column::Fds => match process.fds {
+                Some(fds) if files::near_limit(fds, process.fd_limit) => format!("{fds}!"),
+                Some(fds) => fds.to_string(),
+                None => String::new(),
+            },
+            Column::Pss => process.pss.map(process::format_bytes).unwrap_or_default(),
+            Column::Uss => process.uss.map(process::format_bytes).unwrap_or_default(),
+            Column::Nice => process.nice.to_string(),
+            Column::Policy => match process.policy {
+                Some(policy) if policy.realtime() => {
+                    format!("{}/{}", policy.short_name(), process.rt_priority)
+                }
+                Some(policy) => policy.short_name().to_string(),
+                None => String::new(),
+            },
+            Column::IoPriority => process
+                .io_priority
+                .map(|io| io.to_string())
+                .unwrap_or_default(),
+            Column::Affinity => process
+                .affinity
+                .as_deref()
+                .map(affinity::format_list)
+                .unwrap_or_default(),
+            Column::ReadRate => bytes(process.read_rate.map(|rate| rate as u64)),
+            Column::WriteRate => bytes(process.write_rate.map(|rate| rate as u64)),
+            Column::ReadBytes => bytes(process.read_bytes),
+            Column::WriteBytes => bytes(process.write_bytes),
+            Column::CancelledWriteBytes => bytes(process.cancelled_write_bytes),
+            Column::ReadChars => bytes(process.rchar),
+            Column::WriteChars => bytes(process.wchar),
+            Column::ReadSyscalls => count(process.syscr),
+            Column::WriteSyscalls => count(process.syscw),
+        }
+    }
+
+    pub fn compare(self, a: &ProcessMap, b: &ProcessMap) -> Ordering {
+        // unreadable rates below every measured one, as `None` is for the others
+        let rate = |rate: Option<f64>| rate.unwrap_or(-1.0);
+        match self {
+            Column::Pid => a.pid.cmp(&b.pid),
+            Column::Ppid => a.parent.cmp(&b.parent),
+            Column::Name => a.name.cmp(&b.name),
+            Column::Path => a.path.cmp(&b.path),
+            Column::User => a.user.cmp(&b.user),
+            Column::Cpu => a.cpu.total_cmp(&b.cpu),
+            Column::Rss => a.memory.cmp(&b.memory),
+            Column::Virt => a.virtual_memory.cmp(&b.virtual_memory),
+            Column::Mem => a.memory_percent.total_cmp(&b.memory_percent),
+            Column::State => a.state.cmp(&b.state),
+            Column::Threads => a.threads.cmp(&b.threads),
+            Column::Processor => a.processor.cmp(&b.processor),
+            Column::VoluntarySwitches => a.voluntary_switches.cmp(&b.voluntary_switches),
+            Column::InvoluntarySwitches => a.involuntary_switches.cmp(&b.involuntary_switches),
+            Column::Fds => a.fds.cmp(&b.fds),
+            Column::Pss => a.pss.cmp(&b.pss),
+            Column::Uss => a.uss.cmp(&b.uss),
+            Column::Nice => a.nice.cmp(&b.nice),
+            Column::Policy => (a.policy, a.rt_priority).cmp(&(b.policy, b.rt_priority)),
+            Column::IoPriority => a.io_priority.cmp(&b.io_priority),
+            // the most pinned processes first
+            Column::Affinity => a
+                .affinity
+                .as_ref()
+                .map(Vec::len)
+                .cmp(&b.affinity.as_ref().map(Vec::len))
+                .then_with(|| a.affinity.cmp(&b.affinity)),
+            Column::ReadRate => rate(a.read_rate).total_cmp(&rate(b.read_rate)),
+            Column::WriteRate => rate(a.write_rate).total_cmp(&rate(b.write_rate)),
+            Column::ReadBytes => a.read_bytes.cmp(&b.read_bytes),
+            Column::WriteBytes => a.write_bytes.cmp(&b.write_bytes),
+            Column::CancelledWriteBytes => a.cancelled_write_bytes.cmp(&b.cancelled_write_bytes),
+            Column::ReadChars => a.rchar.cmp(&b.rchar),
+            Column::WriteChars => a.wchar.cmp(&b.wchar),
+            Column::ReadSyscalls => a.syscr.cmp(&b.syscr),
+            Column::WriteSyscalls => a.syscw.cmp(&b.syscw),
+        }
+    }
+}
+
+/// Shrink `wanted` widths to fit in `available` columns, taking from the
+/// widest first and never going below `minimums`. Widths that already fit are
+/// left alone.
+pub fn fit(wanted: &[u16], minimums: &[u16], available: u16) -> Vec<u16> {
+    let widths = |cap: u16| {
+        wanted
+            .iter()
+            .zip(minimums)
+            .map(move |(&width, &minimum)| width.min(cap.max(minimum)))
+    };
+    let mut cap = wanted.iter().copied().max().unwrap_or(0);
+    while cap > 0 && widths(cap).map(u32::from).sum::<u32>() > u32::from(available) {
+        cap -= 1;
+    }
+    widths(cap).collect()
+}
+
+pub fn render_column_editor(f: &mut Frame, app: &mut App, area: Rect) {
+    let label_width = Column::ALL
+        .iter()
+        .map(|column| column.label().len())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<Line> = app
+        .editor_columns()
+        .into_iter()
+        .map(|column| {
+            let shown = if app.columns.contains(&column) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            let width = match app.widths[column.index()] {
+                Some(width) => width.to_string(),
+                None => String::from("auto"),
+            };
+            Line::from(format!(
+                "{shown} {:<label_width$} {width:>5}",
+                column.label()
+            ))
+        })
+        .collect();
+
+    let area = popup_area(area, 54, items.len() as u16 + 2);
+    let editor = List::new(items)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title("Columns")
+                .title_bottom("(Space) show | (J/K) move | (</>) width | (a) auto"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(editor, area, &mut app.column_state);
+}
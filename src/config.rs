@@ -0,0 +1,317 @@
+//! Preferences stored in a TOML file, by default
+//! `$XDG_CONFIG_HOME/rustytop/config.toml`
+//!
+//! ```toml
+//! refresh_interval = 1.5
+//! palette = "emerald"
+//! sort = "cpu"
+//! sort_descending = true
+//! tree = false
+//! threads = false
+//! columns = ["pid", "user", "cpu", "mem", "name"]
+//!
+//! [widths]
+//! name = 40
+//!
+//! [keys]
+//! quit = ["q"]
+//! down = ["j", "Down", "Tab"]
+//!
+//! [themes.dusk]
+//! base = "indigo"
+//! buffer_bg = "#1e1e2e"
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::Options,
+    column::Column,
+    keys::{self, Action},
+    theme::{self, ThemeConfig},
+};
+
+/// The config file as written on disk. Every setting is optional, and
+/// command line options take precedence over it.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Seconds between refreshes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_interval: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    /// Direction of `sort`, the column's natural direction if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_descending: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<bool>,
+    /// List threads along with processes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<bool>,
+    /// Visible columns, in display order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+    /// Column widths in characters, by column name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub widths: BTreeMap<String, u16>,
+    /// Keys of each action, replacing its default keys
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<Action, Vec<String>>,
+    /// User-defined themes, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub themes: BTreeMap<String, ThemeConfig>,
+}
+
+/// Where the config file is read from and saved to when `--config` is not
+/// given, or `None` if neither `XDG_CONFIG_HOME` nor `HOME` is set.
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        // the XDG spec says to ignore relative paths
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("rustytop").join("config.toml"))
+}
+
+impl Config {
+    /// Read the config file at `path`. A missing file is an empty config, so
+    /// running without one uses the defaults.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => return Err(format!("cannot read {}: {err}", path.display())),
+        };
+        toml::from_str(&text).map_err(|err| format!("invalid config {}: {err}", path.display()))
+    }
+
+    /// Write the config to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+
+    /// Fill in the settings not given on the command line from the config.
+    pub fn apply(&self, options: &mut Options) -> Result<(), String> {
+        if options.delay.is_none() {
+            if let Some(seconds) = self.refresh_interval {
+                if !seconds.is_finite() || seconds <= 0.0 {
+                    return Err(format!(
+                        "refresh_interval must be a positive number of seconds, got {seconds}"
+                    ));
+                }
+                options.delay = Some(Duration::from_secs_f64(seconds));
+            }
+        }
+
+        // NO_COLOR outweighs the config file, only `--palette` outweighs it
+        if options.palette.is_none() && !theme::no_color() {
+            options.palette = self.palette.clone();
+        }
+
+        // the direction belongs to the configured column, a column given on
+        // the command line gets its natural direction
+        if options.sort.is_none() {
+            if let Some(name) = &self.sort {
+                options.sort = Some(column(name)?);
+                options.descending = self.sort_descending;
+            }
+        }
+
+        if options.tree.is_none() {
+            options.tree = self.tree;
+        }
+        if options.threads.is_none() {
+            options.threads = self.threads;
+        }
+
+        if let Some(names) = &self.columns {
+            let mut columns = vec![];
+            for name in names {
+                let found = column(name)?;
+                if columns.contains(&found) {
+                    return Err(format!("column '{name}' is listed twice"));
+                }
+                columns.push(found);
+            }
+            if columns.is_empty() {
+                return Err(String::from("columns must list at least one column"));
+            }
+            options.columns = Some(columns);
+        }
+
+        for (name, &width) in &self.widths {
+            options.widths.push((column(name)?, width));
+        }
+
+        for (&action, names) in &self.keys {
+            let codes = names
+                .iter()
+                .map(|name| keys::parse_key(name).ok_or_else(|| format!("unknown key '{name}'")))
+                .collect::<Result<Vec<_>, _>>()?;
+            options.keys.push((action, codes));
+        }
+
+        // a theme can be based on a built-in one or on one defined before it
+        let mut themes = theme::builtin();
+        for (name, theme) in &self.themes {
+            let theme = theme.build(name, &themes)?;
+            themes.push(theme.clone());
+            options.themes.push(theme);
+        }
+
+        Ok(())
+    }
+}
+
+fn column(name: &str) -> Result<Column, String> {
+    Column::from_name(name)
+        .ok_or_else(|| format!("unknown column '{name}', expected {}", Column::names()))
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::crossterm::event::KeyCode;
+
+    use super::*;
+
+    fn config(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    fn apply(text: &str, options: &mut Options) -> Result<(), String> {
+        config(text).apply(options)
+    }
+
+    #[test]
+    fn fills_in_options_not_on_the_command_line() {
+        let mut options = Options::default();
+        apply(
+            r##"
+            refresh_interval = 1.5
+            palette = "emerald"
+            sort = "rss"
+            sort_descending = false
+            tree = true
+            columns = ["pid", "cpu", "name"]
+
+            [widths]
+            name = 40
+
+            [keys]
+            quit = ["Q", "F10"]
+
+            [themes.dusk]
+            base = "indigo"
+            buffer_bg = "#1e1e2e"
+            "##,
+            &mut options,
+        )
+        .unwrap();
+
+        assert_eq!(options.delay, Some(Duration::from_millis(1500)));
+        // NO_COLOR outweighs the config file
+        let palette = (!theme::no_color()).then_some("emerald");
+        assert_eq!(options.palette.as_deref(), palette);
+        assert!(options.sort == Some(Column::Rss));
+        assert_eq!(options.descending, Some(false));
+        assert_eq!(options.tree, Some(true));
+        assert_eq!(options.threads, None);
+        assert!(options.columns == Some(vec![Column::Pid, Column::Cpu, Column::Name]));
+        assert!(options.widths == [(Column::Name, 40)]);
+        assert!(options.keys == [(Action::Quit, vec![KeyCode::Char('Q'), KeyCode::F(10)])]);
+        assert_eq!(options.themes.len(), 1);
+        assert_eq!(options.themes[0].name, "dusk");
+    }
+
+    #[test]
+    fn command_line_options_win() {
+        let mut options =
+            crate::cli::parse(["-d", "3", "-s", "cpu", "--no-tree", "-c", "red"].map(String::from))
+                .unwrap();
+        apply(
+            r#"
+            refresh_interval = 1.5
+            palette = "emerald"
+            sort = "rss"
+            sort_descending = false
+            tree = true
+            "#,
+            &mut options,
+        )
+        .unwrap();
+        assert_eq!(options.delay, Some(Duration::from_secs(3)));
+        assert_eq!(options.palette.as_deref(), Some("red"));
+        assert!(options.sort == Some(Column::Cpu));
+        // the configured direction belongs to the configured column
+        assert_eq!(options.descending, None);
+        assert_eq!(options.tree, Some(false));
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let error = |text: &str| match apply(text, &mut Options::default()) {
+            Ok(()) => panic!("{text} was accepted"),
+            Err(err) => err,
+        };
+        assert_eq!(
+            error("refresh_interval = 0"),
+            "refresh_interval must be a positive number of seconds, got 0"
+        );
+        assert!(error(r#"sort = "colour""#).starts_with("unknown column 'colour', expected pid, "));
+        assert_eq!(
+            error(r#"columns = ["pid", "cpu", "pid"]"#),
+            "column 'pid' is listed twice"
+        );
+        assert_eq!(
+            error("columns = []"),
+            "columns must list at least one column"
+        );
+        assert_eq!(error("[keys]\nquit = [\"Ctrl\"]"), "unknown key 'Ctrl'");
+        assert_eq!(
+            error("[themes.dusk]\nbase = \"dawn\""),
+            "theme 'dusk': unknown base theme 'dawn'"
+        );
+
+        assert!(toml::from_str::<Config>("colour = true").is_err());
+        assert!(toml::from_str::<Config>("[keys]\nteleport = [\"t\"]").is_err());
+    }
+
+    #[test]
+    fn saves_and_loads_back() {
+        let dir = env::temp_dir().join(format!("rustytop-config-{}", std::process::id()));
+        let path = dir.join("nested").join("config.toml");
+        assert!(Config::load(&path).unwrap().palette.is_none());
+
+        let mut saved = config("[keys]\nquit = [\"Q\"]");
+        saved.palette = Some(String::from("red"));
+        saved.columns = Some(vec![String::from("pid"), String::from("name")]);
+        saved.save(&path).unwrap();
+
+        let loaded = Config::load(&path).unwrap();
+        assert_eq!(loaded.palette.as_deref(), Some("red"));
+        assert_eq!(loaded.columns, saved.columns);
+        assert_eq!(loaded.keys, saved.keys);
+        assert_eq!(loaded.refresh_interval, None);
+
+        fs::write(&path, "palette = ").unwrap();
+        match Config::load(&path) {
+            Ok(_) => panic!("a truncated config was loaded"),
+            Err(err) => assert!(err.starts_with(&format!("invalid config {}: ", path.display()))),
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
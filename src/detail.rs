@@ -0,0 +1,521 @@
+//! Detailed view of the selected process, from sysinfo and `/proc/<pid>`
+
+use std::{collections::HashSet, fs};
+
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style, Stylize},
+    terminal::Frame,
+    text::Line,
+    widgets::{Block, BorderType, Clear, Paragraph, Wrap},
+};
+use sysinfo::{Pid, Process, System};
+use users::{get_group_by_gid, get_user_by_uid};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{affinity, popup_area, process::format_bytes, summary::format_uptime, App};
+
+/// Shown for values that could not be read, usually because the process
+/// belongs to another user
+const UNREADABLE: &str = "(not readable)";
+
+/// Memory fields of `/proc/<pid>/status`, with the label they are shown under
+const MEMORY_FIELDS: [(&str, &str); 13] = [
+    ("VmRSS", "Resident"),
+    ("RssAnon", "  anonymous"),
+    ("RssFile", "  file backed"),
+    ("RssShmem", "  shared memory"),
+    ("VmHWM", "Peak resident"),
+    ("VmSize", "Virtual"),
+    ("VmPeak", "Peak virtual"),
+    ("VmData", "Data"),
+    ("VmStk", "Stack"),
+    ("VmExe", "Text"),
+    ("VmLib", "Libraries"),
+    ("VmPTE", "Page tables"),
+    ("VmSwap", "Swapped"),
+];
+
+/// Everything the inspector shows about one process, as titled sections of
+/// label and value pairs
+pub struct Details {
+    pub pid: u32,
+    pub name: String,
+    pub command: String,
+    pub sections: Vec<(&'static str, Vec<(String, String)>)>,
+}
+
+impl Details {
+    /// The details as plain text, as copied to the clipboard.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for (title, entries) in &self.sections {
+            text.push_str(&format!("{title}\n"));
+            for (label, value) in entries {
+                text.push_str(&format!("  {label}: {value}\n"));
+            }
+        }
+        text
+    }
+}
+
+/// Collect the details of `pid`, or `None` if it has exited.
+pub fn inspect(sys: &System, pid: u32) -> Option<Details> {
+    let process = sys.process(Pid::from_u32(pid))?;
+    let status = read_status(pid);
+    let stat = read_stat(pid);
+    let field = |name: &str| {
+        status
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+
+    let command = process.cmd().join(" ");
+    let started = process.start_time();
+    let mut general = vec![
+        (String::from("PID"), pid.to_string()),
+        (String::from("Name"), process.name().to_string()),
+        (
+            String::from("State"),
+            field("State").unwrap_or_else(|| process.status().to_string()),
+        ),
+        (
+            String::from("Executable"),
+            path_or_unreadable(process.exe()),
+        ),
+        (
+            String::from("Command line"),
+            if command.is_empty() {
+                String::from(UNREADABLE)
+            } else {
+                command.clone()
+            },
+        ),
+        (
+            String::from("Working directory"),
+            path_or_unreadable(process.cwd()),
+        ),
+        (String::from("Root"), path_or_unreadable(process.root())),
+        (
+            String::from("Started"),
+            format!(
+                "{} (running for {})",
+                format_time(started),
+                format_uptime(process.run_time())
+            ),
+        ),
+        (
+            String::from("Session"),
+            process
+                .session_id()
+                .map(|session| session.to_string())
+                .unwrap_or_else(|| String::from(UNREADABLE)),
+        ),
+        (
+            String::from("Threads"),
+            field("Threads").unwrap_or_else(|| String::from(UNREADABLE)),
+        ),
+    ];
+    // fields 18 and 19 of /proc/<pid>/stat, counting from the state as 3
+    let stat_field = |n: usize| {
+        stat.get(n - 3)
+            .cloned()
+            .unwrap_or_else(|| String::from("?"))
+    };
+    general.push((String::from("Priority"), stat_field(18)));
+    general.push((String::from("Nice"), stat_field(19)));
+    general.push((
+        String::from("CPU affinity"),
+        match affinity::get(pid) {
+            Ok(cpus) => format!(
+                "{} ({} of {} CPUs)",
+                affinity::format_list(&cpus),
+                cpus.len(),
+                affinity::cpu_count()
+            ),
+            Err(err) => format!("{UNREADABLE}: {err}"),
+        },
+    ));
+
+    let credentials = vec![
+        (
+            String::from("UID (real, effective, saved, fs)"),
+            ids(field("Uid"), |uid| {
+                get_user_by_uid(uid).map(|user| user.name().to_string_lossy().into_owned())
+            }),
+        ),
+        (
+            String::from("GID (real, effective, saved, fs)"),
+            ids(field("Gid"), |gid| {
+                get_group_by_gid(gid).map(|group| group.name().to_string_lossy().into_owned())
+            }),
+        ),
+    ];
+
+    let mut memory = vec![];
+    for (key, label) in MEMORY_FIELDS {
+        if let Some(value) = field(key) {
+            memory.push((String::from(label), format_kilobytes(&value)));
+        }
+    }
+    if memory.is_empty() {
+        // kernel threads have no memory fields, other users' processes do
+        memory.push((String::from("Resident"), format_bytes(process.memory())));
+        memory.push((
+            String::from("Virtual"),
+            format_bytes(process.virtual_memory()),
+        ));
+    }
+
+    let environment = if process.environ().is_empty() {
+        vec![(String::from("Environment"), String::from(UNREADABLE))]
+    } else {
+        process
+            .environ()
+            .iter()
+            .map(|variable| match variable.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => (variable.clone(), String::new()),
+            })
+            .collect()
+    };
+
+    Some(Details {
+        pid,
+        name: process.name().to_string(),
+        command,
+        sections: vec![
+            ("Process", general),
+            ("Parents", parents(sys, process)),
+            ("Credentials", credentials),
+            ("Memory", memory),
+            ("Environment", environment),
+        ],
+    })
+}
+
+/// The ancestors of `process`, from its parent up to init.
+fn parents(sys: &System, process: &Process) -> Vec<(String, String)> {
+    let mut chain = vec![];
+    let mut seen = HashSet::new();
+    let mut parent = process.parent();
+    while let Some(pid) = parent {
+        // PIDs are reused, guard against loops in what sysinfo reports
+        if !seen.insert(pid) {
+            break;
+        }
+        match sys.process(pid) {
+            Some(ancestor) => {
+                chain.push((pid.to_string(), ancestor.name().to_string()));
+                parent = ancestor.parent();
+            }
+            None => {
+                chain.push((pid.to_string(), String::from("?")));
+                break;
+            }
+        }
+    }
+    if chain.is_empty() {
+        chain.push((String::from("Parent"), String::from("none")));
+    }
+    chain
+}
+
+/// The `Key: value` lines of `/proc/<pid>/status`.
+fn read_status(pid: u32) -> Vec<(String, String)> {
+    parse_status(&fs::read_to_string(format!("/proc/{pid}/status")).unwrap_or_default())
+}
+
+fn parse_status(status: &str) -> Vec<(String, String)> {
+    status
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// The fields of `/proc/<pid>/stat` after the command name, which may itself
+/// contain spaces and parentheses.
+fn read_stat(pid: u32) -> Vec<String> {
+    parse_stat(&fs::read_to_string(format!("/proc/{pid}/stat")).unwrap_or_default())
+}
+
+fn parse_stat(stat: &str) -> Vec<String> {
+    match stat.rsplit_once(')') {
+        Some((_, fields)) => fields.split_whitespace().map(String::from).collect(),
+        None => vec![],
+    }
+}
+
+/// Format the four IDs of a `Uid:` or `Gid:` status line with their names.
+fn ids(line: Option<String>, name: impl Fn(u32) -> Option<String>) -> String {
+    let Some(line) = line else {
+        return String::from(UNREADABLE);
+    };
+    let ids: Vec<String> = line
+        .split_whitespace()
+        .map(|id| match id.parse::<u32>().ok().and_then(&name) {
+            Some(name) => format!("{id} ({name})"),
+            None => id.to_string(),
+        })
+        .collect();
+    ids.join(", ")
+}
+
+/// Format a `1234 kB` status value like the table does.
+fn format_kilobytes(value: &str) -> String {
+    match value.trim_end_matches("kB").trim().parse::<u64>() {
+        Ok(kilobytes) => format_bytes(kilobytes * 1024),
+        Err(_) => value.to_string(),
+    }
+}
+
+fn path_or_unreadable(path: Option<&std::path::Path>) -> String {
+    match path {
+        Some(path) if !path.as_os_str().is_empty() => path.to_string_lossy().into_owned(),
+        _ => String::from(UNREADABLE),
+    }
+}
+
+/// Format seconds since the epoch as a local `YYYY-MM-DD HH:MM:SS`.
+fn format_time(seconds: u64) -> String {
+    let time = seconds as libc::time_t;
+    // SAFETY: an all zero tm is valid, and localtime_r only writes to it
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return seconds.to_string();
+    }
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/// The number of rows `lines` take once wrapped to `width` columns, the way
+/// `Paragraph` wraps them without trimming.
+fn wrapped_height(lines: &[Line], width: u16) -> usize {
+    let width = usize::from(width);
+    if width == 0 {
+        return 0;
+    }
+    lines
+        .iter()
+        .map(|line| {
+            let text: String = line
+                .spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect();
+            wrapped_rows(&text, width)
+        })
+        .sum()
+}
+
+/// The number of rows `text` takes once wrapped at word boundaries to
+/// `width` columns, words longer than a row being broken anywhere.
+fn wrapped_rows(text: &str, width: usize) -> usize {
+    let (mut rows, mut column) = (1, 0);
+    let mut rest = text;
+    while !rest.is_empty() {
+        // the spaces up to a word and the word
+        let start = rest
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(rest.len());
+        let end = rest[start..]
+            .find(char::is_whitespace)
+            .map_or(rest.len(), |end| start + end);
+        let spaces = rest[..start].width();
+        let word = rest[start..end].width();
+        rest = &rest[end..];
+
+        if column + spaces + word <= width {
+            column += spaces + word;
+            continue;
+        }
+        // the spaces that fit end the row along with the first one that does
+        // not, the others start the next row
+        let carried = match spaces > width - column && column > 0 {
+            true => spaces - (width - column) - 1,
+            false => 0,
+        };
+        let run = match column {
+            0 => spaces + word,
+            _ => carried + word,
+        };
+        if run == 0 {
+            column = 0;
+            continue;
+        }
+        let extra = (run - 1) / width;
+        rows += usize::from(column > 0) + extra;
+        column = run - extra * width;
+    }
+    rows
+}
+
+pub fn render_details(f: &mut Frame, app: &mut App, area: Rect) {
+    let Some(details) = &app.details else {
+        return;
+    };
+
+    let mut lines: Vec<Line> = vec![];
+    for (title, entries) in &details.sections {
+        lines.push(Line::from(*title).add_modifier(Modifier::BOLD));
+        for (label, value) in entries {
+            lines.push(Line::from(vec![
+                format!("  {label}: ").fg(app.colors.footer_border_color),
+                value.clone().into(),
+            ]));
+        }
+    }
+
+    let area = popup_area(area, area.width.saturating_sub(4), area.height);
+    // scrolling stops at the last line as wrapped to the popup, less its borders
+    app.detail_lines = wrapped_height(&lines, area.width.saturating_sub(2));
+    let view = Paragraph::new(lines).wrap(Wrap { trim: false });
+    app.detail_scroll = app.detail_scroll.min(
+        app.detail_lines
+            .saturating_sub(1)
+            .try_into()
+            .unwrap_or(u16::MAX),
+    );
+    let view = view
+        .scroll((app.detail_scroll, 0))
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title(format!("Process {} ({})", details.pid, details.name))
+                .title_bottom(
+                    "(↑/↓/PgUp/PgDn) scroll | (y) copy all | (c) copy command line | (Esc) close",
+                ),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_widget(view, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn parses_status_lines() {
+        let status = parse_status(
+            "Name:\tmy app\nUmask:\t0022\nState:\tS (sleeping)\n\
+             Uid:\t1000\t1000\t1000\t1000\nVmRSS:\t    5120 kB\nbad line\n",
+        );
+        let pairs: Vec<(&str, &str)> = status
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("Name", "my app"),
+                ("Umask", "0022"),
+                ("State", "S (sleeping)"),
+                ("Uid", "1000\t1000\t1000\t1000"),
+                ("VmRSS", "5120 kB"),
+            ]
+        );
+        assert!(parse_status("").is_empty());
+    }
+
+    #[test]
+    fn parses_stat_after_the_name() {
+        let fields = parse_stat("77 (a) b (c)) S 1 77 77 0 -1 4194560 12 0 0 0 3 1 0 0 20 0 1");
+        assert_eq!(fields[0], "S");
+        assert_eq!(fields[1], "1");
+        // fields 18 and 19 as the view counts them
+        assert_eq!(fields[18 - 3], "20");
+        assert_eq!(fields[19 - 3], "0");
+        assert!(parse_stat("").is_empty());
+    }
+
+    #[test]
+    fn formats_ids_with_names() {
+        let name = |id: u32| (id == 0).then(|| String::from("root"));
+        assert_eq!(
+            ids(Some(String::from("0\t0\t1000\t0")), name),
+            "0 (root), 0 (root), 1000, 0 (root)"
+        );
+        assert_eq!(ids(None, name), UNREADABLE);
+    }
+
+    #[test]
+    fn formats_status_values() {
+        assert_eq!(format_kilobytes("5120 kB"), "5.0M");
+        assert_eq!(format_kilobytes("0 kB"), "0B");
+        assert_eq!(format_kilobytes("unknown"), "unknown");
+        assert_eq!(path_or_unreadable(Some(Path::new("/bin/sh"))), "/bin/sh");
+        assert_eq!(path_or_unreadable(Some(Path::new(""))), UNREADABLE);
+        assert_eq!(path_or_unreadable(None), UNREADABLE);
+    }
+
+    #[test]
+    fn counts_rows_as_paragraph_wraps_them() {
+        use ratatui::{buffer::Buffer, widgets::Widget};
+
+        let texts = [
+            "",
+            "Process",
+            "  PID: 1234",
+            "  Command line: /usr/lib/firefox/firefox -contentproc -childID 12 -prefsLen 31337",
+            "  LS_COLORS: rs=0:di=01;34:ln=01;36:mh=00:pi=40;33:so=01;35:do=01;35:bd=40;33;01",
+            "  Working directory: /home/someone/a directory name with spaces in it",
+            "  SPACED:    out   with  runs    of     spaces      ",
+            "  TRAILING: value        ",
+        ];
+        for text in texts {
+            for width in 4..60 {
+                // the row the marker line lands on is the height of the text
+                let lines = vec![Line::from(text), Line::from("#")];
+                let area = Rect::new(0, 0, width, 100);
+                let mut buffer = Buffer::empty(area);
+                Paragraph::new(lines.clone())
+                    .wrap(Wrap { trim: false })
+                    .render(area, &mut buffer);
+                let rendered = (0..area.height)
+                    .position(|y| buffer.get(0, y).symbol() == "#")
+                    .unwrap();
+                assert_eq!(
+                    wrapped_height(&lines[..1], width),
+                    rendered,
+                    "{text:?} at width {width}"
+                );
+            }
+        }
+        assert_eq!(wrapped_height(&[Line::from("text")], 0), 0);
+    }
+
+    #[test]
+    fn copies_sections_as_text() {
+        let details = Details {
+            pid: 1,
+            name: String::from("init"),
+            command: String::from("/sbin/init"),
+            sections: vec![
+                ("Process", vec![(String::from("PID"), String::from("1"))]),
+                (
+                    "Parents",
+                    vec![(String::from("Parent"), String::from("none"))],
+                ),
+            ],
+        };
+        assert_eq!(
+            details.text(),
+            "Process\n  PID: 1\nParents\n  Parent: none\n"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Disk I/O: the counters of each process from `/proc/<pid>/io`, and the
+//! throughput of the mounted disks from `/proc/diskstats`
+
+use std::{collections::HashMap, fs};
+
+use sysinfo::Disks;
+
+/// `/proc/diskstats` counts in 512 byte sectors whatever the device
+const SECTOR_SIZE: u64 = 512;
+
+/// The counters of `/proc/<pid>/io`, since the task started
+#[derive(Clone, Copy, Default)]
+pub struct IoCounters {
+    /// Bytes passed to `read` and the like, from the page cache or not
+    pub rchar: u64,
+    /// Bytes passed to `write` and the like
+    pub wchar: u64,
+    pub syscr: u64,
+    pub syscw: u64,
+    /// Bytes the task caused to be fetched from storage
+    pub read_bytes: u64,
+    /// Bytes the task caused to be sent to storage
+    pub write_bytes: u64,
+    /// Bytes written to the page cache then truncated before reaching storage
+    pub cancelled_write_bytes: u64,
+}
+
+/// Read the counters of a process, or of a thread with
+/// `/proc/<pid>/task/<tid>/io`. `None` if the file cannot be read, which
+/// takes the rights to trace the task.
+pub fn read_counters(path: &str) -> Option<IoCounters> {
+    Some(parse_counters(&fs::read_to_string(path).ok()?))
+}
+
+/// The counters in the text of an `io` file, 0 for those missing.
+fn parse_counters(io: &str) -> IoCounters {
+    let mut counters = IoCounters::default();
+    for (key, value) in io.lines().filter_map(|line| line.split_once(':')) {
+        let field = match key {
+            "rchar" => &mut counters.rchar,
+            "wchar" => &mut counters.wchar,
+            "syscr" => &mut counters.syscr,
+            "syscw" => &mut counters.syscw,
+            "read_bytes" => &mut counters.read_bytes,
+            "write_bytes" => &mut counters.write_bytes,
+            "cancelled_write_bytes" => &mut counters.cancelled_write_bytes,
+            _ => continue,
+        };
+        *field = value.trim().parse().unwrap_or(0);
+    }
+    counters
+}
+
+/// Kernel names of the block devices `disks` are mounted from, such as `sda1`
+/// or `dm-0`, each once. Pseudo filesystems, which have no device, are left
+/// out.
+pub fn mounted_devices(disks: &Disks) -> Vec<String> {
+    let mut devices = vec![];
+    for disk in disks.list() {
+        // device mapper and `/dev/root` names are links to the kernel's
+        let Ok(device) = fs::canonicalize(disk.name()) else {
+            continue;
+        };
+        if let Some(name) = device.file_name().and_then(|name| name.to_str()) {
+            if device.starts_with("/dev") && !devices.iter().any(|known| known == name) {
+                devices.push(name.to_string());
+            }
+        }
+    }
+    devices
+}
+
+/// Bytes read from and written to `devices` since boot.
+pub fn device_totals(devices: &[String]) -> Option<(u64, u64)> {
+    Some(sum_devices(
+        &fs::read_to_string("/proc/diskstats").ok()?,
+        devices,
+    ))
+}
+
+/// Bytes read from and written to `devices` according to the text of
+/// `/proc/diskstats`. Devices it does not list count for nothing.
+fn sum_devices(stats: &str, devices: &[String]) -> (u64, u64) {
+    // major minor name reads merged sectors_read ms writes merged sectors_written ...
+    let sectors: HashMap<&str, (u64, u64)> = stats
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let read = fields.get(5)?.parse().ok()?;
+            let written = fields.get(9)?.parse().ok()?;
+            Some((*fields.get(2)?, (read, written)))
+        })
+        .collect();
+
+    let (mut read, mut written) = (0, 0);
+    for (device_read, device_written) in devices
+        .iter()
+        .filter_map(|device| sectors.get(device.as_str()))
+    {
+        read += device_read * SECTOR_SIZE;
+        written += device_written * SECTOR_SIZE;
+    }
+    (read, written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_io_counters() {
+        let counters = parse_counters(
+            "rchar: 4292\n\
+             wchar: 1024\n\
+             syscr: 13\n\
+             syscw: 2\n\
+             read_bytes: 8192\n\
+             write_bytes: 4096\n\
+             cancelled_write_bytes: 512\n",
+        );
+        assert_eq!(counters.rchar, 4292);
+        assert_eq!(counters.wchar, 1024);
+        assert_eq!(counters.syscr, 13);
+        assert_eq!(counters.syscw, 2);
+        assert_eq!(counters.read_bytes, 8192);
+        assert_eq!(counters.write_bytes, 4096);
+        assert_eq!(counters.cancelled_write_bytes, 512);
+
+        // unknown and malformed lines are skipped, missing counters are 0
+        let counters = parse_counters("rchar: 7\nfuture_field: 3\nwchar: lots\nno colon\n");
+        assert_eq!(counters.rchar, 7);
+        assert_eq!(counters.wchar, 0);
+        assert_eq!(counters.read_bytes, 0);
+    }
+
+    #[test]
+    fn reads_the_own_counters() {
+        let counters = read_counters("/proc/self/io").unwrap();
+        assert!(counters.rchar > 0);
+        assert!(read_counters("/proc/self/no-such-file").is_none());
+    }
+
+    #[test]
+    fn sums_the_sectors_of_the_devices() {
+        let stats = "\
+ 253       0 vda 9717 3431 1042650 6245 2562 3074 107240 6092 0 9700 12337 0 0 0 0 0 0
+ 253      16 vdb 322 0 18682 108 2 0 8 0 0 93 108 0 0 0 0 0 0
+   7       0 loop0 5 0 10 1 0 0 0 0 0 4 1
+ 253      32 vdc short
+";
+        let devices =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        assert_eq!(
+            sum_devices(stats, &devices(&["vda"])),
+            (1042650 * 512, 107240 * 512)
+        );
+        assert_eq!(
+            sum_devices(stats, &devices(&["vda", "vdb", "vdc", "sda"])),
+            ((1042650 + 18682) * 512, (107240 + 8) * 512)
+        );
+        assert_eq!(sum_devices(stats, &[]), (0, 0));
+    }
+}
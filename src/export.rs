@@ -0,0 +1,109 @@
+//! Structured export of process snapshots as JSON, CSV and NDJSON
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use ratatui::{
+    layout::Rect,
+    style::Style,
+    terminal::Frame,
+    text::Line,
+    widgets::{Block, BorderType, Clear, Paragraph},
+};
+
+use crate::{popup_area, process::ProcessMap, App};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Plain text table, only for batch output
+    Table,
+    /// One JSON array per snapshot
+    Json,
+    /// A header line, then one line per process
+    Csv,
+    /// One JSON object per process and per line
+    Ndjson,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let format = match name.to_lowercase().as_str() {
+            "table" | "text" => Format::Table,
+            "json" => Format::Json,
+            "csv" => Format::Csv,
+            "ndjson" | "jsonl" => Format::Ndjson,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// Guess the format from a file extension, defaulting to JSON.
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Format::from_name)
+            .filter(|format| *format != Format::Table)
+            .unwrap_or(Format::Json)
+    }
+}
+
+/// Write `processes` to `out` in `format`. The CSV header is only written if
+/// `header` is set, so successive snapshots form a single CSV stream.
+pub fn write_records<W: Write>(
+    out: &mut W,
+    format: Format,
+    processes: &[&ProcessMap],
+    header: bool,
+) -> io::Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, processes)?;
+            writeln!(out)
+        }
+        Format::Ndjson => {
+            for process in processes {
+                serde_json::to_writer(&mut *out, process)?;
+                writeln!(out)?;
+            }
+            Ok(())
+        }
+        Format::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(header)
+                .from_writer(&mut *out);
+            for process in processes {
+                writer.serialize(process).map_err(io::Error::other)?;
+            }
+            writer.flush()
+        }
+        Format::Table => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the table format is only available in batch mode",
+        )),
+    }
+}
+
+/// Write `processes` to the file at `path`, in the format its extension names.
+pub fn export_file(path: &Path, processes: &[&ProcessMap]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_records(&mut out, Format::from_path(path), processes, true)?;
+    out.flush()
+}
+
+pub fn render_export_prompt(f: &mut Frame, app: &App, area: Rect) {
+    let area = popup_area(area, 60, 3);
+    let prompt = Paragraph::new(Line::from(format!("File: {}█", app.export_path)))
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title("Export current view (.json, .csv or .ndjson)"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_widget(prompt, area);
+}
@@ -0,0 +1,256 @@
+//! Open file descriptors of a process, from `/proc/<pid>/fd` and
+//! `/proc/<pid>/fdinfo`, like `lsof -p`
+
+use std::{
+    fs::{self, FileType},
+    io,
+    os::unix::fs::FileTypeExt,
+};
+
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    terminal::Frame,
+    widgets::{Block, BorderType, Clear, Row, Table},
+};
+
+use crate::{popup_area, App};
+
+/// Share of `RLIMIT_NOFILE` from which a process is warned about
+const NEAR_LIMIT: f64 = 0.8;
+
+/// One open file descriptor
+pub struct OpenFile {
+    pub fd: u32,
+    /// What the descriptor refers to: `file`, `dir`, `chr`, `blk`, `fifo`,
+    /// `pipe`, `socket`, `eventfd` and other anonymous inodes
+    pub kind: String,
+    /// The path, or the kernel's description such as `socket:[12345]`
+    pub target: String,
+    /// `r`, `w` or `rw`
+    pub mode: String,
+    pub offset: u64,
+}
+
+/// The open descriptors of `pid`, by ascending number.
+pub fn list(pid: u32) -> io::Result<Vec<OpenFile>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(format!("/proc/{pid}/fd"))? {
+        let entry = entry?;
+        let Some(fd) = entry.file_name().to_str().and_then(|fd| fd.parse().ok()) else {
+            continue;
+        };
+        // the descriptor may be closed between listing and reading it
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        let target = target.to_string_lossy().into_owned();
+        let kind = kind(
+            &target,
+            fs::metadata(entry.path()).ok().map(|m| m.file_type()),
+        );
+
+        let (mode, offset) = fs::read_to_string(format!("/proc/{pid}/fdinfo/{fd}"))
+            .map(|info| parse_fdinfo(&info))
+            .unwrap_or_default();
+
+        files.push(OpenFile {
+            fd,
+            kind,
+            target,
+            mode,
+            offset,
+        });
+    }
+    files.sort_by_key(|file| file.fd);
+    Ok(files)
+}
+
+/// The access mode and offset in the text of an `fdinfo` file.
+fn parse_fdinfo(info: &str) -> (String, u64) {
+    let (mut mode, mut offset) = (String::new(), 0);
+    for (key, value) in info.lines().filter_map(|line| line.split_once(':')) {
+        match key {
+            "pos" => offset = value.trim().parse().unwrap_or(0),
+            // octal open flags, the access mode in the lowest two bits
+            "flags" => {
+                mode = match u32::from_str_radix(value.trim(), 8).map(|flags| flags & 3) {
+                    Ok(0) => String::from("r"),
+                    Ok(1) => String::from("w"),
+                    Ok(2) => String::from("rw"),
+                    _ => String::from("?"),
+                }
+            }
+            _ => {}
+        }
+    }
+    (mode, offset)
+}
+
+fn kind(target: &str, file_type: Option<FileType>) -> String {
+    if let Some(kind) = target.strip_prefix("anon_inode:") {
+        // anon_inode:[eventfd] and anon_inode:inotify
+        return kind.trim_matches(|c| c == '[' || c == ']').to_string();
+    }
+    if let Some((kind, _)) = target.split_once(":[") {
+        // pipe:[1234] and socket:[1234]
+        return kind.to_string();
+    }
+    let kind = match file_type {
+        Some(file_type) if file_type.is_dir() => "dir",
+        Some(file_type) if file_type.is_char_device() => "chr",
+        Some(file_type) if file_type.is_block_device() => "blk",
+        Some(file_type) if file_type.is_fifo() => "fifo",
+        Some(file_type) if file_type.is_socket() => "socket",
+        _ => "file",
+    };
+    kind.to_string()
+}
+
+/// Number of open descriptors of `pid`, if it can be read.
+pub fn count(pid: u32) -> Option<u32> {
+    Some(fs::read_dir(format!("/proc/{pid}/fd")).ok()?.count() as u32)
+}
+
+/// The soft `RLIMIT_NOFILE` of `pid`, from `/proc/<pid>/limits` which unlike
+/// `prlimit` can be read for processes of other users.
+pub fn limit(pid: u32) -> Option<u64> {
+    parse_limit(&fs::read_to_string(format!("/proc/{pid}/limits")).ok()?)
+}
+
+/// The soft limit of open files in the text of a `limits` file.
+fn parse_limit(limits: &str) -> Option<u64> {
+    let line = limits
+        .lines()
+        .find(|line| line.starts_with("Max open files"))?;
+    // "unlimited" does not parse, and there is nothing to be near then
+    line["Max open files".len()..]
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Whether `count` descriptors are close to the `limit`.
+pub fn near_limit(count: u32, limit: Option<u64>) -> bool {
+    limit.is_some_and(|limit| count as f64 >= limit as f64 * NEAR_LIMIT)
+}
+
+pub fn render_files(f: &mut Frame, app: &mut App, area: Rect) {
+    let header = ["FD", "MODE", "OFFSET", "TYPE", "TARGET"]
+        .into_iter()
+        .collect::<Row>()
+        .style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .fg(app.colors.header_fg)
+                .bg(app.colors.header_bg),
+        );
+    let rows = app.open_files.iter().map(|file| {
+        Row::new([
+            file.fd.to_string(),
+            file.mode.clone(),
+            file.offset.to_string(),
+            file.kind.clone(),
+            file.target.clone(),
+        ])
+    });
+    let widths = [
+        Constraint::Length(6),
+        Constraint::Length(4),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Fill(1),
+    ];
+
+    let count = app.open_files.len() as u32;
+    let mut title = format!("Open files of process {}: {count}", app.files_pid);
+    if let Some(limit) = app.files_limit {
+        title.push_str(&format!(" of at most {limit}"));
+    }
+    let mut border = Style::new().fg(app.colors.footer_border_color);
+    if near_limit(count, app.files_limit) {
+        title.push_str(" - close to the limit");
+        border = border.fg(app.colors.removed_row_fg);
+    }
+
+    let area = popup_area(area, area.width.saturating_sub(4), area.height);
+    let table = Table::new(rows, widths)
+        .header(header)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(border)
+                .title(title)
+                .title_bottom("(↑/↓) move | (Esc) close"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut app.files_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_targets() {
+        let file_type = |path: &str| fs::metadata(path).ok().map(|m| m.file_type());
+        assert_eq!(kind("socket:[12345]", None), "socket");
+        assert_eq!(kind("pipe:[678]", None), "pipe");
+        assert_eq!(kind("anon_inode:[eventfd]", None), "eventfd");
+        assert_eq!(kind("anon_inode:inotify", None), "inotify");
+        assert_eq!(kind("/", file_type("/")), "dir");
+        assert_eq!(kind("/dev/null", file_type("/dev/null")), "chr");
+        let exe = std::env::current_exe().unwrap();
+        let exe = exe.to_str().unwrap();
+        assert_eq!(kind(exe, file_type(exe)), "file");
+        // deleted files keep their path but cannot be looked at
+        assert_eq!(kind("/tmp/log (deleted)", None), "file");
+    }
+
+    #[test]
+    fn parses_access_modes() {
+        let mode = |flags: &str| parse_fdinfo(&format!("pos:\t0\nflags:\t{flags}\n")).0;
+        assert_eq!(mode("0100000"), "r");
+        assert_eq!(mode("02100001"), "w");
+        assert_eq!(mode("0102"), "rw");
+        assert_eq!(mode("03"), "?");
+        assert_eq!(mode("9"), "?");
+        assert_eq!(
+            parse_fdinfo("pos:\t4096\nflags:\t02\nmnt_id:\t26\nino:\t1234\n"),
+            (String::from("rw"), 4096)
+        );
+        assert_eq!(parse_fdinfo(""), (String::new(), 0));
+    }
+
+    #[test]
+    fn parses_open_file_limit() {
+        let limits = |files: &str| {
+            format!(
+                "Limit                     Soft Limit           Hard Limit           Units     \n\
+                 Max cpu time              unlimited            unlimited            seconds   \n\
+                 Max open files            {files:<20} 524288               files     \n\
+                 Max locked memory         8388608              8388608              bytes     \n"
+            )
+        };
+        assert_eq!(parse_limit(&limits("1024")), Some(1024));
+        assert_eq!(parse_limit(&limits("unlimited")), None);
+        assert_eq!(parse_limit("Limit  Soft Limit  Hard Limit  Units\n"), None);
+    }
+
+    #[test]
+    fn warns_from_80_percent() {
+        assert!(!near_limit(818, Some(1024)));
+        assert!(near_limit(820, Some(1024)));
+        assert!(near_limit(1024, Some(1024)));
+        assert!(near_limit(0, Some(0)));
+        assert!(!near_limit(u32::MAX, None));
+    }
+}
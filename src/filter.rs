@@ -0,0 +1,950 @@
+//! Filters typed in the filter pane
+//!
+//! A filter is a small query, for example
+//! `user=postgres and cpu>5 and name~"worker"`, made of:
+//!
+//! * comparisons `field op value` where `field` is one of `pid`, `ppid`,
+//!   `name`, `path` (or `file`), `user`, `cpu`, `rss`, `virt`, `mem` and
+//!   `port`, and `op` is one of `=`, `!=`, `~` (matches), `!~` (does not
+//!   match), `<`, `<=`, `>` and `>=`. Ordering operators only apply to the
+//!   numeric fields; `rss` and `virt` accept sizes such as `512K` or `1.5G`,
+//!   and `port` compares any of the ports a process listens on
+//! * bare patterns, matched against the column the filter was opened for (or
+//!   every column)
+//! * `and`, `or`, `not` (or `!`) and parentheses. Terms written next to each
+//!   other are combined with `and`
+//!
+//! Patterns, both bare and after `~`, are either text, matched as a substring
+//! and ignoring case when the text is all lowercase (like vim's smartcase), or
+//! `/regex/`, with `/regex/i` ignoring case. Text containing spaces or
+//! operators can be quoted with `"`.
+
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::process::ProcessMap;
+
+/// A column of the process table that can appear in a comparison
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Pid,
+    Ppid,
+    Name,
+    Path,
+    User,
+    Cpu,
+    Rss,
+    Virt,
+    Mem,
+    /// The TCP and UDP ports a process listens on
+    Port,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name.to_lowercase().as_str() {
+            "pid" => Field::Pid,
+            "ppid" => Field::Ppid,
+            "name" => Field::Name,
+            "path" | "file" | "exe" => Field::Path,
+            "user" => Field::User,
+            "cpu" => Field::Cpu,
+            "rss" => Field::Rss,
+            "virt" => Field::Virt,
+            "mem" => Field::Mem,
+            "port" => Field::Port,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::Pid => "pid",
+            Field::Ppid => "ppid",
+            Field::Name => "name",
+            Field::Path => "path",
+            Field::User => "user",
+            Field::Cpu => "cpu",
+            Field::Rss => "rss",
+            Field::Virt => "virt",
+            Field::Mem => "mem",
+            Field::Port => "port",
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        !matches!(self, Field::Name | Field::Path | Field::User)
+    }
+
+    fn number(self, process: &ProcessMap) -> f64 {
+        match self {
+            Field::Pid => process.pid as f64,
+            Field::Ppid => process.parent.unwrap_or(0) as f64,
+            Field::Cpu => process.cpu as f64,
+            Field::Rss => process.memory as f64,
+            Field::Virt => process.virtual_memory as f64,
+            Field::Mem => process.memory_percent as f64,
+            Field::Name | Field::Path | Field::User | Field::Port => 0.0,
+        }
+    }
+
+    fn text(self, process: &ProcessMap) -> String {
+        match self {
+            Field::Name => process.name.clone(),
+            Field::Path => process.path.clone(),
+            Field::User => process.user.clone(),
+            Field::Pid | Field::Ppid => (self.number(process) as u64).to_string(),
+            Field::Cpu | Field::Rss | Field::Virt | Field::Mem => self.number(process).to_string(),
+            Field::Port => process
+                .listening
+                .iter()
+                .map(|port| port.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Match,
+    NotMatch,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Match => "~",
+            Op::NotMatch => "!~",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+}
+
+pub enum Pattern {
+    Text { needle: String, ignore_case: bool },
+    Regex { regex: Regex, ignore_case: bool },
+}
+
+impl Pattern {
+    fn text(text: &str) -> Self {
+        let ignore_case = !text.chars().any(char::is_uppercase);
+        Pattern::Text {
+            needle: if ignore_case {
+                text.to_lowercase()
+            } else {
+                text.to_string()
+            },
+            ignore_case,
+        }
+    }
+
+    fn regex(expression: &str, ignore_case: bool) -> Result<Self, String> {
+        RegexBuilder::new(expression)
+            .case_insensitive(ignore_case)
+            .build()
+            .map(|regex| Pattern::Regex { regex, ignore_case })
+            .map_err(|err| {
+                // regex errors span several lines pointing at the problem, the
+                // last one says what it is
+                let err = err.to_string();
+                let reason = err.lines().last().unwrap_or_default().trim();
+                format!("invalid regex /{expression}/: {reason}")
+            })
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        match self {
+            Pattern::Text {
+                needle,
+                ignore_case: true,
+            } => value.to_lowercase().contains(needle.as_str()),
+            Pattern::Text { needle, .. } => value.contains(needle.as_str()),
+            Pattern::Regex { regex, .. } => regex.is_match(value),
+        }
+    }
+}
+
+pub enum Value {
+    Number(f64),
+    Text(String),
+    Pattern(Pattern),
+}
+
+/// Parsed form of a filter query
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        field: Field,
+        op: Op,
+        value: Value,
+    },
+    /// A bare pattern, matched against the filter's default column
+    Bare(Pattern),
+}
+
+impl Expr {
+    fn eval<S: AsRef<str>>(
+        &self,
+        process: &ProcessMap,
+        cells: &[S],
+        default: Option<Field>,
+    ) -> bool {
+        match self {
+            Expr::And(left, right) => {
+                left.eval(process, cells, default) && right.eval(process, cells, default)
+            }
+            Expr::Or(left, right) => {
+                left.eval(process, cells, default) || right.eval(process, cells, default)
+            }
+            Expr::Not(inner) => !inner.eval(process, cells, default),
+            Expr::Bare(pattern) => match default {
+                Some(field) => pattern.is_match(&field.text(process)),
+                None => cells.iter().any(|cell| pattern.is_match(cell.as_ref())),
+            },
+            Expr::Compare { field, op, value } => match value {
+                Value::Pattern(pattern) => {
+                    pattern.is_match(&field.text(process)) == (*op == Op::Match)
+                }
+                Value::Text(text) => (field.text(process) == *text) == (*op == Op::Eq),
+                Value::Number(number) => {
+                    let compare = |actual: f64| match op {
+                        Op::Eq => actual == *number,
+                        Op::Ne => actual != *number,
+                        Op::Lt => actual < *number,
+                        Op::Le => actual <= *number,
+                        Op::Gt => actual > *number,
+                        Op::Ge => actual >= *number,
+                        Op::Match | Op::NotMatch => false,
+                    };
+                    match field {
+                        Field::Port => process.listening.iter().any(|&port| compare(port as f64)),
+                        field => compare(field.number(process)),
+                    }
+                }
+            },
+        }
+    }
+
+    /// Whether any comparison of the expression is on `field`.
+    fn uses(&self, field: Field) -> bool {
+        match self {
+            Expr::And(left, right) | Expr::Or(left, right) => left.uses(field) || right.uses(field),
+            Expr::Not(inner) => inner.uses(field),
+            Expr::Compare {
+                field: compared, ..
+            } => *compared == field,
+            Expr::Bare(_) => false,
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(..) => 0,
+            Expr::And(..) => 1,
+            _ => 2,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter, min_precedence: u8) -> fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Text { needle, .. } => write!(f, "{}", quote(needle)),
+            Pattern::Regex {
+                regex,
+                ignore_case: true,
+            } => write!(f, "/{regex}/i"),
+            Pattern::Regex { regex, .. } => write!(f, "/{regex}/"),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::And(left, right) => {
+                left.fmt_operand(f, 1)?;
+                write!(f, " and ")?;
+                right.fmt_operand(f, 1)
+            }
+            Expr::Or(left, right) => {
+                left.fmt_operand(f, 0)?;
+                write!(f, " or ")?;
+                right.fmt_operand(f, 0)
+            }
+            Expr::Not(inner) => {
+                write!(f, "not ")?;
+                inner.fmt_operand(f, 2)
+            }
+            Expr::Bare(pattern) => write!(f, "{pattern}"),
+            Expr::Compare { field, op, value } => {
+                write!(f, "{}{}", field.name(), op.symbol())?;
+                match value {
+                    Value::Number(number) => write!(f, "{number}"),
+                    Value::Text(text) => write!(f, "{}", quote(text)),
+                    Value::Pattern(pattern) => write!(f, "{pattern}"),
+                }
+            }
+        }
+    }
+}
+
+pub struct Filter {
+    expr: Expr,
+    /// Column bare patterns are matched against, every column if `None`
+    default: Option<Field>,
+}
+
+impl Filter {
+    /// Parse `text`, matching bare patterns against `default`, or against all
+    /// columns if it is `None`.
+    pub fn parse(text: &str, default: Option<Field>) -> Result<Self, String> {
+        let tokens = lex(text)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            end: text.chars().count(),
+        };
+        let expr = parser.parse_or()?;
+        if let Some((token, at)) = parser.tokens.get(parser.position) {
+            return Err(error(*at, format!("unexpected {}", token.describe())));
+        }
+        Ok(Self { expr, default })
+    }
+
+    /// Whether a process passes the filter. `cells` are the values displayed
+    /// for it, used when bare patterns are matched against every column.
+    pub fn matches<S: AsRef<str>>(&self, process: &ProcessMap, cells: &[S]) -> bool {
+        self.expr.eval(process, cells, self.default)
+    }
+
+    /// Whether the filter needs the listening ports of the processes, which
+    /// are only looked up while it does.
+    pub fn uses_ports(&self) -> bool {
+        self.expr.uses(Field::Port)
+    }
+}
+
+/// All of `filters` as one query, the way they are applied: each one narrows
+/// the ones before it.
+pub fn describe(filters: &[Filter]) -> String {
+    filters
+        .iter()
+        .map(|filter| match filter.expr {
+            Expr::Or(..) if filters.len() > 1 => format!("({filter})"),
+            _ => filter.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.expr, self.default) {
+            (Expr::Bare(pattern), Some(field)) => write!(f, "{}~{pattern}", field.name()),
+            (expr, _) => write!(f, "{expr}"),
+        }
+    }
+}
+
+fn error(at: usize, message: String) -> String {
+    format!("column {}: {message}", at + 1)
+}
+
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Op(Op),
+    Word(String),
+    Quoted(String),
+    Regex(String, bool),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::LParen => String::from("'('"),
+            Token::RParen => String::from("')'"),
+            Token::And => String::from("'and'"),
+            Token::Or => String::from("'or'"),
+            Token::Not => String::from("'not'"),
+            Token::Op(op) => format!("'{}'", op.symbol()),
+            Token::Word(word) => format!("'{word}'"),
+            Token::Quoted(text) => quote(text),
+            Token::Regex(expression, _) => format!("/{expression}/"),
+        }
+    }
+}
+
+/// Quote `text` so a query matches it literally, whatever characters it holds.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Split a query into tokens, each with the character position it starts at.
+fn lex(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                tokens.push((Token::LParen, start));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((Token::RParen, start));
+                i += 1;
+            }
+            '=' => {
+                tokens.push((Token::Op(Op::Eq), start));
+                i += 1;
+            }
+            '~' => {
+                tokens.push((Token::Op(Op::Match), start));
+                i += 1;
+            }
+            '!' if next == Some('=') => {
+                tokens.push((Token::Op(Op::Ne), start));
+                i += 2;
+            }
+            '!' if next == Some('~') => {
+                tokens.push((Token::Op(Op::NotMatch), start));
+                i += 2;
+            }
+            '!' => {
+                tokens.push((Token::Not, start));
+                i += 1;
+            }
+            '<' | '>' => {
+                let op = match (chars[i], next == Some('=')) {
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    (_, false) => Op::Gt,
+                    (_, true) => Op::Ge,
+                };
+                i += if next == Some('=') { 2 } else { 1 };
+                tokens.push((Token::Op(op), start));
+            }
+            '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(error(start, String::from("unterminated quote"))),
+                        Some('"') => break,
+                        Some('\\') if chars.get(i + 1).is_some() => {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&c) => {
+                            value.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push((Token::Quoted(value), start));
+            }
+            '/' if regex_end(&chars, i).is_some() => {
+                let (end, ignore_case) = regex_end(&chars, i).unwrap_or((i, false));
+                let expression: String = chars[i + 1..end].iter().collect();
+                i = end + if ignore_case { 2 } else { 1 };
+                tokens.push((Token::Regex(expression, ignore_case), start));
+            }
+            _ => {
+                while i < chars.len() && !ends_word(&chars, i) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.to_lowercase().as_str() {
+                    "and" | "&&" => Token::And,
+                    "or" | "||" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                };
+                tokens.push((token, start));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// If a regex literal starts at `start`, the position of its closing slash
+/// and whether it is followed by the `i` flag. The closing slash must end the
+/// token, so paths like `/usr/bin/bash` are read as plain words.
+fn regex_end(chars: &[char], start: usize) -> Option<(usize, bool)> {
+    let ends_token = |i: usize| {
+        chars
+            .get(i)
+            .is_none_or(|c| c.is_whitespace() || *c == ')' || *c == '(')
+    };
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '/' if ends_token(i + 1) => return Some((i, false)),
+            '/' if chars.get(i + 1) == Some(&'i') && ends_token(i + 2) => return Some((i, true)),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn ends_word(chars: &[char], i: usize) -> bool {
+    match chars[i] {
+        c if c.is_whitespace() => true,
+        '(' | ')' | '"' | '=' | '~' | '<' | '>' => true,
+        '!' => matches!(chars.get(i + 1), Some('=') | Some('~')),
+        _ => false,
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Length of the query, reported as the position of errors at its end
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn at(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(_, at)| *at)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_and()?;
+        while let Some(Token::Or) = self.peek() {
+            self.position += 1;
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.position += 1,
+                Some(Token::Not | Token::LParen | Token::Word(_))
+                | Some(Token::Quoted(_) | Token::Regex(..)) => {}
+                _ => break,
+            }
+            let right = self.parse_unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if let Some(Token::Not) = self.peek() {
+            self.position += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr, String> {
+        let at = self.at();
+        let Some((token, _)) = self.tokens.get(self.position) else {
+            return Err(error(at, String::from("expected a filter")));
+        };
+        self.position += 1;
+
+        match token {
+            Token::LParen => {
+                let expr = self.parse_or()?;
+                match self.peek() {
+                    Some(Token::RParen) => {
+                        self.position += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(error(self.at(), String::from("missing ')'"))),
+                }
+            }
+            Token::Word(word) => {
+                if let Some(Token::Op(op)) = self.peek() {
+                    let (word, op) = (word.clone(), *op);
+                    let field = Field::from_name(&word).ok_or_else(|| {
+                        error(
+                            at,
+                            format!(
+                                "unknown field '{word}', expected pid, ppid, name, path, user, cpu, rss, virt, mem or port"
+                            ),
+                        )
+                    })?;
+                    self.position += 1;
+                    return self.parse_comparison(field, op);
+                }
+                Ok(Expr::Bare(Pattern::text(word)))
+            }
+            Token::Quoted(text) => Ok(Expr::Bare(Pattern::text(text))),
+            Token::Regex(expression, ignore_case) => Pattern::regex(expression, *ignore_case)
+                .map(Expr::Bare)
+                .map_err(|err| error(at, err)),
+            Token::Op(op) => Err(error(
+                at,
+                format!("expected a field before '{}'", op.symbol()),
+            )),
+            token => Err(error(
+                at,
+                format!("expected a filter before {}", token.describe()),
+            )),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: Field, op: Op) -> Result<Expr, String> {
+        let at = self.at();
+        let missing = || {
+            error(
+                at,
+                format!("expected a value after '{}{}'", field.name(), op.symbol()),
+            )
+        };
+        let Some((token, _)) = self.tokens.get(self.position) else {
+            return Err(missing());
+        };
+
+        let value = match (token, op) {
+            (Token::Regex(expression, ignore_case), Op::Match | Op::NotMatch) => Value::Pattern(
+                Pattern::regex(expression, *ignore_case).map_err(|err| error(at, err))?,
+            ),
+            (Token::Word(text) | Token::Quoted(text), Op::Match | Op::NotMatch) => {
+                Value::Pattern(Pattern::text(text))
+            }
+            (Token::Regex(..), _) => {
+                return Err(error(
+                    at,
+                    format!("a regex needs '~' or '!~', not '{}'", op.symbol()),
+                ))
+            }
+            (Token::Word(text) | Token::Quoted(text), _) if field.is_numeric() => {
+                Value::Number(parse_number(field, text).map_err(|err| error(at, err))?)
+            }
+            (Token::Word(text) | Token::Quoted(text), Op::Eq | Op::Ne) => Value::Text(text.clone()),
+            (Token::Word(_) | Token::Quoted(_), _) => {
+                return Err(error(
+                    at,
+                    format!(
+                        "'{}' only works on numeric fields, {} is text",
+                        op.symbol(),
+                        field.name()
+                    ),
+                ))
+            }
+            _ => return Err(missing()),
+        };
+        self.position += 1;
+
+        Ok(Expr::Compare { field, op, value })
+    }
+}
+
+/// Parse a number for `field`: sizes such as `512K` or `1.5G` for the memory
+/// columns, and an optional `%` for the percentages.
+fn parse_number(field: Field, text: &str) -> Result<f64, String> {
+    let not_a_number = || format!("expected a number for {}, got '{text}'", field.name());
+    let trimmed = text.trim();
+    let trimmed = match field {
+        Field::Cpu | Field::Mem => trimmed.strip_suffix('%').unwrap_or(trimmed),
+        _ => trimmed,
+    };
+    if let Ok(number) = trimmed.parse::<f64>() {
+        return Ok(number);
+    }
+    if !matches!(field, Field::Rss | Field::Virt) {
+        return Err(not_a_number());
+    }
+
+    let upper = trimmed.to_uppercase();
+    let upper = upper
+        .strip_suffix("IB")
+        .or_else(|| upper.strip_suffix('B'))
+        .unwrap_or(&upper);
+    if let Ok(bytes) = upper.parse::<f64>() {
+        return Ok(bytes);
+    }
+    // the unit is the last character, which may take several bytes
+    let Some((split, unit)) = upper.char_indices().next_back() else {
+        return Err(not_a_number());
+    };
+    let number = upper[..split].parse::<f64>().map_err(|_| not_a_number())?;
+    let multiplier = match unit {
+        'K' => 1024.0,
+        'M' => 1024.0 * 1024.0,
+        'G' => 1024.0 * 1024.0 * 1024.0,
+        'T' => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => {
+            return Err(format!(
+                "unknown size unit in '{text}', expected K, M, G or T"
+            ))
+        }
+    };
+    Ok(number * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<(String, usize)> {
+        lex(text)
+            .unwrap()
+            .iter()
+            .map(|(token, at)| (token.describe(), *at))
+            .collect()
+    }
+
+    fn shown(text: &str) -> String {
+        Filter::parse(text, None).unwrap().to_string()
+    }
+
+    fn parse_error(text: &str) -> String {
+        match Filter::parse(text, None) {
+            Ok(filter) => panic!("{text} parsed as {filter}"),
+            Err(err) => err,
+        }
+    }
+
+    fn process(name: &str, user: &str, cpu: f32, memory: u64) -> ProcessMap {
+        ProcessMap {
+            pid: 42,
+            name: name.to_string(),
+            path: format!("/usr/bin/{name}"),
+            user: user.to_string(),
+            cpu,
+            memory,
+            ..ProcessMap::default()
+        }
+    }
+
+    fn matches(text: &str, process: &ProcessMap) -> bool {
+        Filter::parse(text, None)
+            .unwrap()
+            .matches(process, &[process.name.as_str(), process.user.as_str()])
+    }
+
+    #[test]
+    fn quoted_text_lexes_back_unchanged() {
+        for text in [
+            "",
+            "plain",
+            "/usr/bin/",
+            r#"a "b" \c\"#,
+            "x and (y) or !z~/r/",
+        ] {
+            let quoted = quote(text);
+            match lex(&quoted).unwrap().as_slice() {
+                [(Token::Quoted(value), 0)] => assert_eq!(value, text),
+                _ => panic!("{quoted} is not a single quoted token"),
+            }
+        }
+        assert_eq!(shown(r#"name="a\"b""#), r#"name="a\"b""#);
+    }
+
+    #[test]
+    fn lex_operators_and_words() {
+        assert_eq!(
+            tokens("user=root and cpu>=5 || !(x!~y)"),
+            [
+                ("'user'", 0),
+                ("'='", 4),
+                ("'root'", 5),
+                ("'and'", 10),
+                ("'cpu'", 14),
+                ("'>='", 17),
+                ("'5'", 19),
+                ("'or'", 21),
+                ("'not'", 24),
+                ("'('", 25),
+                ("'x'", 26),
+                ("'!~'", 27),
+                ("'y'", 29),
+                ("')'", 30),
+            ]
+            .map(|(token, at)| (token.to_string(), at))
+        );
+    }
+
+    #[test]
+    fn lex_quotes_and_regexes() {
+        assert_eq!(
+            tokens(r#""a \"b\" c" /ba.h/i /usr/bin/bash"#),
+            [
+                (r#""a \"b\" c""#, 0),
+                ("/ba.h/", 12),
+                ("'/usr/bin/bash'", 20),
+            ]
+            .map(|(token, at)| (token.to_string(), at))
+        );
+        assert!(lex(r#"name="open"#).is_err_and(|err| err == "column 6: unterminated quote"));
+    }
+
+    #[test]
+    fn lex_counts_characters_not_bytes() {
+        assert_eq!(
+            tokens("é~ü x"),
+            [("'é'", 0), ("'~'", 1), ("'ü'", 2), ("'x'", 4)]
+                .map(|(token, at)| (token.to_string(), at))
+        );
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(shown("a or b and c"), r#""a" or "b" and "c""#);
+        assert_eq!(shown("(a or b) and c"), r#"("a" or "b") and "c""#);
+        assert_eq!(shown("a b or c"), r#""a" and "b" or "c""#);
+        assert_eq!(shown("not a or b"), r#"not "a" or "b""#);
+        assert_eq!(shown("not (a or b)"), r#"not ("a" or "b")"#);
+        assert_eq!(shown("cpu>5 && rss<1K"), "cpu>5 and rss<1024");
+    }
+
+    #[test]
+    fn describes_every_active_filter() {
+        let filters = [
+            Filter::parse("root", Some(Field::User)).unwrap(),
+            Filter::parse("cpu>5 or rss>1G", None).unwrap(),
+            Filter::parse("not ssh", Some(Field::Path)).unwrap(),
+        ];
+        assert_eq!(describe(&[]), "");
+        assert_eq!(describe(&filters[1..2]), "cpu>5 or rss>1073741824");
+        assert_eq!(
+            describe(&filters),
+            r#"user~"root" and (cpu>5 or rss>1073741824) and not "ssh""#
+        );
+    }
+
+    #[test]
+    fn evaluates_queries() {
+        let postgres = process("postgres", "postgres", 12.5, 300 << 20);
+        assert!(matches("user=postgres and cpu>5", &postgres));
+        assert!(!matches("user=postgres and cpu>50", &postgres));
+        assert!(matches("cpu>50 or rss>=200M", &postgres));
+        assert!(matches("name~/^post/ and not name~bash", &postgres));
+        assert!(matches("path~/usr/bin/", &postgres));
+        // smartcase: lowercase ignores case, uppercase does not
+        assert!(!matches("POST", &process("Postgres", "root", 0.0, 0)));
+        assert!(matches("post", &process("Postgres", "root", 0.0, 0)));
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(
+            parse_error("cpu>"),
+            "column 5: expected a value after 'cpu>'"
+        );
+        assert_eq!(
+            parse_error("a and foo=1"),
+            "column 7: unknown field 'foo', expected pid, ppid, name, path, user, cpu, rss, virt, mem or port"
+        );
+        assert_eq!(parse_error("(a or b"), "column 8: missing ')'");
+        assert_eq!(parse_error("a )"), "column 3: unexpected ')'");
+        assert_eq!(parse_error("=3"), "column 1: expected a field before '='");
+        assert_eq!(
+            parse_error("name<x"),
+            "column 6: '<' only works on numeric fields, name is text"
+        );
+        assert_eq!(
+            parse_error("name~é and cpu>x"),
+            "column 16: expected a number for cpu, got 'x'"
+        );
+        assert!(parse_error("name~/(/").starts_with("column 6: invalid regex /(/"));
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_number(Field::Rss, "512"), Ok(512.0));
+        assert_eq!(parse_number(Field::Rss, "512K"), Ok(512.0 * 1024.0));
+        assert_eq!(
+            parse_number(Field::Rss, "1.5g"),
+            Ok(1.5 * 1024.0 * 1024.0 * 1024.0)
+        );
+        assert_eq!(
+            parse_number(Field::Virt, "10MiB"),
+            Ok(10.0 * 1024.0 * 1024.0)
+        );
+        assert_eq!(parse_number(Field::Virt, "2kb"), Ok(2048.0));
+        assert_eq!(parse_number(Field::Cpu, "5%"), Ok(5.0));
+        assert_eq!(parse_number(Field::Pid, "12"), Ok(12.0));
+    }
+
+    #[test]
+    fn parse_bad_sizes() {
+        assert_eq!(
+            parse_number(Field::Rss, "5X"),
+            Err(String::from(
+                "unknown size unit in '5X', expected K, M, G or T"
+            ))
+        );
+        assert!(parse_number(Field::Rss, "K").is_err());
+        assert!(parse_number(Field::Rss, "").is_err());
+        assert!(parse_number(Field::Pid, "5K").is_err());
+        assert!(parse_number(Field::Cpu, "five").is_err());
+    }
+
+    #[test]
+    fn parse_multibyte_units_without_panicking() {
+        assert_eq!(
+            parse_number(Field::Rss, "5é"),
+            Err(String::from(
+                "unknown size unit in '5é', expected K, M, G or T"
+            ))
+        );
+        assert!(parse_number(Field::Virt, "1€").is_err());
+        assert!(parse_number(Field::Virt, "€").is_err());
+        assert_eq!(
+            parse_error("rss>5é"),
+            "column 5: unknown size unit in '5é', expected K, M, G or T"
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! Which processes use a file or directory, like `fuser`: as an open
+//! descriptor, a memory mapping, their working directory, root or executable
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    terminal::Frame,
+    text::Line,
+    widgets::{Block, BorderType, Clear, Paragraph, Row, Table},
+};
+
+use crate::{
+    popup_area,
+    process::{format_bytes, ProcessMap},
+    App,
+};
+
+/// The kernel appends this to the links of files removed while open
+const DELETED: &str = " (deleted)";
+
+/// One process using one path
+pub struct Holder {
+    pub pid: u32,
+    pub name: String,
+    pub user: String,
+    /// How the path is used, in `fuser`'s letters: `f` open file, `m` mapped,
+    /// `c` working directory, `r` root directory, `e` executable
+    pub access: String,
+    pub path: String,
+    pub deleted: bool,
+    /// Size of a deleted file still held open, the disk space it keeps
+    pub size: Option<u64>,
+    /// Device and inode of a deleted file, the same for every process holding
+    /// it
+    pub file: Option<(u64, u64)>,
+}
+
+/// Result of a lookup
+pub struct Lookup {
+    pub holders: Vec<Holder>,
+    /// Processes whose files could not be read, usually for lack of privileges
+    pub unreadable: usize,
+}
+
+/// The path as the user typed it, made absolute and resolved if it exists.
+/// Deleted files no longer exist and are matched as typed.
+pub fn resolve(input: &str) -> PathBuf {
+    let input = input.trim();
+    let path = match input.strip_prefix("~/") {
+        Some(rest) => std::env::var_os("HOME")
+            .map(|home| Path::new(&home).join(rest))
+            .unwrap_or_else(|| PathBuf::from(input)),
+        None => PathBuf::from(input),
+    };
+    fs::canonicalize(&path).unwrap_or_else(|_| {
+        std::env::current_dir()
+            .map(|dir| dir.join(&path))
+            .unwrap_or(path)
+    })
+}
+
+/// Find the processes using `path` or anything below it if it is a directory.
+/// Without a path, find every process holding a deleted file.
+pub fn find(processes: &[ProcessMap], path: Option<&Path>) -> Lookup {
+    let mut holders = vec![];
+    let mut unreadable = 0;
+
+    for process in processes.iter().filter(|process| !process.thread) {
+        // (path, deleted) -> (access letters, metadata of a deleted file)
+        let mut uses: BTreeMap<(String, bool), (String, Option<fs::Metadata>)> = BTreeMap::new();
+        let mut add = |target: &str, access: char, meta: Option<fs::Metadata>| {
+            let (target, deleted) = match target.strip_suffix(DELETED) {
+                Some(target) => (target, true),
+                None => (target, false),
+            };
+            let matched = match path {
+                Some(path) => Path::new(target).starts_with(path),
+                None => deleted,
+            };
+            if matched {
+                let entry = uses.entry((target.to_string(), deleted)).or_default();
+                if !entry.0.contains(access) {
+                    entry.0.push(access);
+                }
+                entry.1 = entry.1.take().or(meta);
+            }
+        };
+
+        let pid = process.pid;
+        let mut readable = false;
+        for (link, access) in [("exe", 'e'), ("cwd", 'c'), ("root", 'r')] {
+            if let Ok(target) = fs::read_link(format!("/proc/{pid}/{link}")) {
+                readable = true;
+                add(&target.to_string_lossy(), access, None);
+            }
+        }
+        if let Ok(entries) = fs::read_dir(format!("/proc/{pid}/fd")) {
+            readable = true;
+            for entry in entries.filter_map(Result::ok) {
+                let Ok(target) = fs::read_link(entry.path()) else {
+                    continue;
+                };
+                let target = target.to_string_lossy();
+                // only look the size up for deleted files, the ones it matters for
+                let meta = match target.ends_with(DELETED) {
+                    true => fs::metadata(entry.path()).ok(),
+                    false => None,
+                };
+                add(&target, 'f', meta);
+            }
+        }
+        if let Ok(maps) = fs::read_to_string(format!("/proc/{pid}/maps")) {
+            for line in maps.lines() {
+                if let Some(target) = mapped_path(line) {
+                    add(target, 'm', None);
+                }
+            }
+        }
+        // processes that exited since the last refresh are not counted
+        if !readable
+            && process.path != crate::process::KERNEL_THREAD
+            && Path::new(&format!("/proc/{pid}")).exists()
+        {
+            unreadable += 1;
+        }
+
+        for ((target, deleted), (access, meta)) in uses {
+            holders.push(Holder {
+                pid,
+                name: process.name.clone(),
+                user: process.user.clone(),
+                access,
+                path: target,
+                deleted,
+                size: meta.as_ref().map(|meta| meta.len()),
+                file: meta.map(|meta| (meta.dev(), meta.ino())),
+            });
+        }
+    }
+
+    holders.sort_by(|a, b| a.pid.cmp(&b.pid).then_with(|| a.path.cmp(&b.path)));
+    Lookup {
+        holders,
+        unreadable,
+    }
+}
+
+/// The file of a `/proc/<pid>/maps` line, the sixth field, which may contain
+/// spaces. Anonymous mappings and `[heap]` like pseudo paths have none.
+fn mapped_path(line: &str) -> Option<&str> {
+    let mut rest = line;
+    for _ in 0..5 {
+        rest = rest.trim_start().split_once(' ')?.1;
+    }
+    Some(rest.trim_start()).filter(|path| path.starts_with('/'))
+}
+
+/// Disk space the deleted files of `holders` keep, counting each file once
+/// however many processes hold it.
+fn held_size(holders: &[Holder]) -> u64 {
+    let mut files = HashSet::new();
+    holders
+        .iter()
+        .filter_map(|holder| Some((holder.file?, holder.size?)))
+        .filter(|(file, _)| files.insert(*file))
+        .map(|(_, size)| size)
+        .sum()
+}
+
+pub fn render_path_prompt(f: &mut Frame, app: &App, area: Rect) {
+    let area = popup_area(area, 70, 4);
+    let prompt = Paragraph::new(vec![
+        Line::from(format!("Path: {}█", app.fuser_input)),
+        Line::from("leave empty to find deleted files still held open"),
+    ])
+    .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+    .block(
+        Block::bordered()
+            .border_type(BorderType::Double)
+            .border_style(Style::new().fg(app.colors.footer_border_color))
+            .title("Find processes using a file or directory"),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(prompt, area);
+}
+
+pub fn render_holders(f: &mut Frame, app: &mut App, area: Rect) {
+    let header = ["PID", "NAME", "USER", "USE", "SIZE", "PATH"]
+        .into_iter()
+        .collect::<Row>()
+        .style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .fg(app.colors.header_fg)
+                .bg(app.colors.header_bg),
+        );
+    let rows = app.holders.iter().map(|holder| {
+        let mut path = holder.path.clone();
+        if holder.deleted {
+            path.push_str(DELETED);
+        }
+        let row = Row::new([
+            holder.pid.to_string(),
+            holder.name.clone(),
+            holder.user.clone(),
+            holder.access.clone(),
+            holder.size.map(format_bytes).unwrap_or_default(),
+            path,
+        ]);
+        match holder.deleted {
+            true => row.style(Style::new().fg(app.colors.removed_row_fg)),
+            false => row,
+        }
+    });
+    let widths = [
+        Constraint::Length(8),
+        Constraint::Length(16),
+        Constraint::Length(10),
+        Constraint::Length(5),
+        Constraint::Length(7),
+        Constraint::Fill(1),
+    ];
+
+    let processes = {
+        let mut pids: Vec<u32> = app.holders.iter().map(|holder| holder.pid).collect();
+        pids.dedup();
+        pids.len()
+    };
+    let mut title = match app.fuser_path.as_os_str().is_empty() {
+        true => format!(
+            "{processes} processes hold {} of deleted files",
+            format_bytes(held_size(&app.holders))
+        ),
+        false => format!("{processes} processes use {}", app.fuser_path.display()),
+    };
+    if app.fuser_unreadable > 0 {
+        title.push_str(&format!(" - {} not readable", app.fuser_unreadable));
+    }
+
+    let area = popup_area(area, area.width.saturating_sub(4), area.height);
+    let table = Table::new(rows, widths)
+        .header(header)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title(title)
+                .title_bottom(
+                    "(f) file (m) mapped (c) cwd (r) root (e) exe | (Enter) select process | (r) rescan | (Esc) close",
+                ),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut app.holders_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holder(pid: u32, file: Option<(u64, u64)>, size: Option<u64>) -> Holder {
+        Holder {
+            pid,
+            name: String::from("test"),
+            user: String::from("root"),
+            access: String::from("f"),
+            path: String::from("/tmp/file"),
+            deleted: file.is_some(),
+            size,
+            file,
+        }
+    }
+
+    #[test]
+    fn held_size_counts_each_file_once() {
+        let holders = [
+            holder(1, Some((8, 100)), Some(1000)),
+            holder(2, Some((8, 100)), Some(1000)),
+            holder(2, Some((8, 101)), Some(24)),
+            // another device, same inode number
+            holder(3, Some((9, 100)), Some(5)),
+            holder(3, None, None),
+        ];
+        assert_eq!(held_size(&holders), 1029);
+        assert_eq!(held_size(&[]), 0);
+    }
+
+    #[test]
+    fn mapped_path_of_maps_lines() {
+        assert_eq!(
+            mapped_path("7f00-7f10 r-xp 00000000 08:01 1234   /usr/lib/libc.so.6"),
+            Some("/usr/lib/libc.so.6")
+        );
+        assert_eq!(
+            mapped_path("7f00-7f10 rw-p 00000000 08:01 99 /tmp/with space (deleted)"),
+            Some("/tmp/with space (deleted)")
+        );
+        assert_eq!(
+            mapped_path("7f00-7f10 rw-p 00000000 00:00 0      [heap]"),
+            None
+        );
+        assert_eq!(mapped_path("7f00-7f10 rw-p 00000000 00:00 0"), None);
+    }
+}
@@ -0,0 +1,54 @@
+//! The key help popup, listing every action with its keys as the keymap binds
+//! them
+
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    terminal::Frame,
+    widgets::{Block, BorderType, Clear, Row, Table},
+};
+
+use crate::{keys, popup_area, App};
+
+pub fn render_help(f: &mut Frame, app: &mut App, area: Rect) {
+    let header = ["KEYS", "ACTION"].into_iter().collect::<Row>().style(
+        Style::new()
+            .add_modifier(Modifier::BOLD)
+            .fg(app.colors.header_fg)
+            .bg(app.colors.header_bg),
+    );
+    let rows = app.keymap.bindings().iter().map(|(action, codes)| {
+        let keys = codes
+            .iter()
+            .map(|&code| keys::key_name(code))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let row = Row::new([keys, action.description().to_string()]);
+        if codes.is_empty() {
+            row.style(Style::new().add_modifier(Modifier::DIM))
+        } else {
+            row
+        }
+    });
+    let widths = [Constraint::Length(20), Constraint::Fill(1)];
+
+    let area = popup_area(area, 60, app.keymap.bindings().len() as u16 + 3);
+    let table = Table::new(rows, widths)
+        .header(header)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title("Keys")
+                .title_bottom("(↑/↓) move | (Esc) close"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut app.help_state);
+}
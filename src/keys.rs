@@ -0,0 +1,366 @@
+//! Key bindings of the process table
+
+use std::collections::HashMap;
+
+use ratatui::crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+
+/// Something a key can be bound to in the process table. The names in the
+/// `[keys]` table of the config file are the snake_case variant names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Down,
+    Up,
+    Inspect,
+    FilterFile,
+    FilterUser,
+    FilterAll,
+    ClearFilters,
+    Sort,
+    ReverseSort,
+    Tree,
+    Threads,
+    ThreadList,
+    OpenFiles,
+    FileUsers,
+    Sockets,
+    ListeningPort,
+    MemoryMap,
+    Priority,
+    Affinity,
+    Collapse,
+    Expand,
+    Tag,
+    TagMatching,
+    InvertTags,
+    UntagAll,
+    Signal,
+    Export,
+    Slower,
+    Faster,
+    Columns,
+    Palette,
+    SaveConfig,
+    Help,
+}
+
+impl Action {
+    /// What the action does, for the key help
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Down => "move down",
+            Action::Up => "move up",
+            Action::Inspect => "details",
+            Action::FilterFile => "filter by file",
+            Action::FilterUser => "filter by user",
+            Action::FilterAll => "filter all columns",
+            Action::ClearFilters => "clear filters",
+            Action::Sort => "sort column",
+            Action::ReverseSort => "reverse sort",
+            Action::Tree => "tree view",
+            Action::Threads => "show threads",
+            Action::ThreadList => "thread list",
+            Action::OpenFiles => "open files",
+            Action::FileUsers => "find file users",
+            Action::Sockets => "sockets",
+            Action::ListeningPort => "filter by listening port",
+            Action::MemoryMap => "memory map",
+            Action::Priority => "priority",
+            Action::Affinity => "CPU affinity",
+            Action::Collapse => "collapse subtree",
+            Action::Expand => "expand subtree",
+            Action::Tag => "tag",
+            Action::TagMatching => "tag all shown",
+            Action::InvertTags => "invert tags",
+            Action::UntagAll => "untag all",
+            Action::Signal => "send signal",
+            Action::Export => "export",
+            Action::Slower => "slower refresh",
+            Action::Faster => "faster refresh",
+            Action::Columns => "columns",
+            Action::Palette => "next palette",
+            Action::SaveConfig => "save layout",
+            Action::Help => "key help",
+        }
+    }
+}
+
+/// Actions named in the footer, the rest being listed in the key help
+const FOOTER_ACTIONS: [Action; 4] = [Action::Quit, Action::FilterAll, Action::Sort, Action::Help];
+
+const DEFAULT_KEYS: [(Action, &[KeyCode]); 34] = [
+    (Action::Quit, &[KeyCode::Char('q'), KeyCode::Esc]),
+    (Action::Down, &[KeyCode::Char('j'), KeyCode::Down]),
+    (Action::Up, &[KeyCode::Char('k'), KeyCode::Up]),
+    (Action::Inspect, &[KeyCode::Enter]),
+    (Action::FilterFile, &[KeyCode::Char('f'), KeyCode::Insert]),
+    (Action::FilterUser, &[KeyCode::Char('u')]),
+    (Action::FilterAll, &[KeyCode::Char('/')]),
+    (Action::ClearFilters, &[KeyCode::Char('c')]),
+    (Action::Sort, &[KeyCode::Char('s')]),
+    (Action::ReverseSort, &[KeyCode::Char('r')]),
+    (Action::Tree, &[KeyCode::Char('t')]),
+    (Action::Threads, &[KeyCode::Char('H')]),
+    (Action::ThreadList, &[KeyCode::Char('T')]),
+    (Action::OpenFiles, &[KeyCode::Char('L')]),
+    (Action::FileUsers, &[KeyCode::Char('F')]),
+    (Action::Sockets, &[KeyCode::Char('N')]),
+    (Action::ListeningPort, &[KeyCode::Char('P')]),
+    (Action::MemoryMap, &[KeyCode::Char('M')]),
+    (Action::Collapse, &[KeyCode::Char('h'), KeyCode::Left]),
+    (Action::Expand, &[KeyCode::Char('l'), KeyCode::Right]),
+    (Action::Tag, &[KeyCode::Char(' ')]),
+    (Action::TagMatching, &[KeyCode::Char('A')]),
+    (Action::InvertTags, &[KeyCode::Char('I')]),
+    (Action::UntagAll, &[KeyCode::Char('U')]),
+    (Action::Signal, &[KeyCode::Char('x'), KeyCode::F(9)]),
+    (Action::Priority, &[KeyCode::Char('n'), KeyCode::F(7)]),
+    (Action::Affinity, &[KeyCode::Char('a')]),
+    (Action::Export, &[KeyCode::Char('e')]),
+    (Action::Slower, &[KeyCode::Char('+')]),
+    (Action::Faster, &[KeyCode::Char('-')]),
+    (Action::Columns, &[KeyCode::Char('o')]),
+    (Action::Palette, &[KeyCode::Char('p')]),
+    (Action::SaveConfig, &[KeyCode::Char('w')]),
+    (Action::Help, &[KeyCode::Char('?'), KeyCode::F(1)]),
+];
+
+/// Which action each key triggers
+pub struct Keymap {
+    actions: HashMap<KeyCode, Action>,
+    /// The keys left to each action, in the order of `DEFAULT_KEYS`
+    bindings: Vec<(Action, Vec<KeyCode>)>,
+}
+
+impl Keymap {
+    /// The default bindings, with the keys of every action in `overrides`
+    /// replaced by the given ones. A key bound to two actions triggers the one
+    /// from `overrides`.
+    pub fn new(overrides: &[(Action, Vec<KeyCode>)]) -> Self {
+        let mut keys = HashMap::new();
+        for (action, codes) in DEFAULT_KEYS {
+            if overrides
+                .iter()
+                .all(|(overridden, _)| *overridden != action)
+            {
+                keys.extend(codes.iter().map(|&code| (code, action)));
+            }
+        }
+        for (action, codes) in overrides {
+            keys.extend(codes.iter().map(|&code| (code, *action)));
+        }
+
+        let bindings = DEFAULT_KEYS
+            .iter()
+            .map(|&(action, defaults)| {
+                let codes = overrides
+                    .iter()
+                    .find(|(overridden, _)| *overridden == action)
+                    .map_or(defaults, |(_, codes)| codes.as_slice());
+                let mut bound = vec![];
+                for &code in codes {
+                    if keys.get(&code) == Some(&action) && !bound.contains(&code) {
+                        bound.push(code);
+                    }
+                }
+                (action, bound)
+            })
+            .collect();
+        Keymap {
+            actions: keys,
+            bindings,
+        }
+    }
+
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.actions.get(&code).copied()
+    }
+
+    /// Every action with the keys that trigger it, possibly none.
+    pub fn bindings(&self) -> &[(Action, Vec<KeyCode>)] {
+        &self.bindings
+    }
+
+    /// A one line reminder of the main keys, such as `(q) quit | (?) key help`.
+    /// Actions left without a key are skipped.
+    pub fn footer(&self) -> String {
+        self.bindings
+            .iter()
+            .filter(|(action, _)| FOOTER_ACTIONS.contains(action))
+            .filter_map(|(action, codes)| {
+                let key = key_name(*codes.first()?);
+                Some(format!("({key}) {}", action.description()))
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&[])
+    }
+}
+
+/// Parse a key as written in the config file: a single character, or a name
+/// such as `Esc`, `Enter`, `Space`, `Up` or `F9`, ignoring case.
+pub fn parse_key(text: &str) -> Option<KeyCode> {
+    let mut chars = text.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let code = match text.to_lowercase().as_str() {
+        "esc" | "escape" => KeyCode::Esc,
+        "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "insert" | "ins" => KeyCode::Insert,
+        "delete" | "del" => KeyCode::Delete,
+        name => {
+            let number = name.strip_prefix('f')?.parse::<u8>().ok()?;
+            if !(1..=24).contains(&number) {
+                return None;
+            }
+            KeyCode::F(number)
+        }
+    };
+    Some(code)
+}
+
+/// Name a key the way `parse_key` reads it.
+pub fn key_name(code: KeyCode) -> String {
+    let name = match code {
+        KeyCode::Char(' ') => "Space",
+        KeyCode::Char(c) => return c.to_string(),
+        KeyCode::F(number) => return format!("F{number}"),
+        KeyCode::Esc => "Esc",
+        KeyCode::Enter => "Enter",
+        KeyCode::Tab => "Tab",
+        KeyCode::BackTab => "BackTab",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Up => "Up",
+        KeyCode::Down => "Down",
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Home => "Home",
+        KeyCode::End => "End",
+        KeyCode::PageUp => "PageUp",
+        KeyCode::PageDown => "PageDown",
+        KeyCode::Insert => "Insert",
+        KeyCode::Delete => "Delete",
+        other => return format!("{other:?}"),
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn footer_follows_the_keymap() {
+        assert_eq!(
+            Keymap::default().footer(),
+            "(q) quit | (/) filter all columns | (s) sort column | (?) key help"
+        );
+
+        let keymap = Keymap::new(&[
+            (Action::Quit, vec![KeyCode::Char('Q')]),
+            (Action::Sort, vec![]),
+            (Action::Help, vec![KeyCode::F(1)]),
+        ]);
+        assert_eq!(
+            keymap.footer(),
+            "(Q) quit | (/) filter all columns | (F1) key help"
+        );
+    }
+
+    #[test]
+    fn bindings_leave_out_keys_taken_by_overrides() {
+        let keymap = Keymap::new(&[(Action::Tree, vec![KeyCode::Char('s')])]);
+        let keys_of = |wanted| {
+            keymap
+                .bindings()
+                .iter()
+                .find(|(action, _)| *action == wanted)
+                .map(|(_, codes)| codes.clone())
+                .unwrap()
+        };
+        assert_eq!(keys_of(Action::Tree), [KeyCode::Char('s')]);
+        assert_eq!(keys_of(Action::Sort), []);
+        assert_eq!(keymap.action(KeyCode::Char('s')), Some(Action::Tree));
+        assert_eq!(keymap.action(KeyCode::Char('t')), None);
+        assert_eq!(keymap.bindings().len(), DEFAULT_KEYS.len());
+    }
+
+    #[test]
+    fn parses_key_names() {
+        assert_eq!(parse_key("q"), Some(KeyCode::Char('q')));
+        assert_eq!(parse_key("Q"), Some(KeyCode::Char('Q')));
+        assert_eq!(parse_key("é"), Some(KeyCode::Char('é')));
+        assert_eq!(parse_key("ESC"), Some(KeyCode::Esc));
+        assert_eq!(parse_key("return"), Some(KeyCode::Enter));
+        assert_eq!(parse_key("Space"), Some(KeyCode::Char(' ')));
+        assert_eq!(parse_key("PageDown"), Some(KeyCode::PageDown));
+        assert_eq!(parse_key("f9"), Some(KeyCode::F(9)));
+        assert_eq!(parse_key("F24"), Some(KeyCode::F(24)));
+        assert_eq!(parse_key("F0"), None);
+        assert_eq!(parse_key("F25"), None);
+        assert_eq!(parse_key("Fx"), None);
+        assert_eq!(parse_key("ctrl"), None);
+        assert_eq!(parse_key(""), None);
+    }
+
+    #[test]
+    fn key_names_parse_back() {
+        for (_, codes) in DEFAULT_KEYS {
+            for &code in codes {
+                assert_eq!(parse_key(&key_name(code)), Some(code));
+            }
+        }
+        for name in [
+            "Tab",
+            "BackTab",
+            "Backspace",
+            "Home",
+            "End",
+            "PageUp",
+            "Delete",
+        ] {
+            assert_eq!(key_name(parse_key(name).unwrap()), name);
+        }
+    }
+
+    #[test]
+    fn overrides_replace_the_default_keys() {
+        let default = Keymap::default();
+        assert_eq!(default.action(KeyCode::Char('q')), Some(Action::Quit));
+        assert_eq!(default.action(KeyCode::Esc), Some(Action::Quit));
+        assert_eq!(default.action(KeyCode::F(9)), Some(Action::Signal));
+        assert_eq!(default.action(KeyCode::Char('z')), None);
+
+        let keymap = Keymap::new(&[
+            (Action::Quit, vec![KeyCode::Char('Q')]),
+            (Action::Down, vec![KeyCode::Char('q'), KeyCode::Tab]),
+        ]);
+        assert_eq!(keymap.action(KeyCode::Char('Q')), Some(Action::Quit));
+        assert_eq!(keymap.action(KeyCode::Esc), None);
+        assert_eq!(keymap.action(KeyCode::Char('q')), Some(Action::Down));
+        assert_eq!(keymap.action(KeyCode::Tab), Some(Action::Down));
+        assert_eq!(keymap.action(KeyCode::Char('j')), None);
+        assert_eq!(keymap.action(KeyCode::Up), Some(Action::Up));
+    }
+}
@@ -1,165 +1,1126 @@
 //! # [Rustytop] A rust based tool to display running processes
 
-use std::{error::Error, io};
+mod affinity;
+mod batch;
+mod cli;
+mod clipboard;
+mod column;
+mod config;
+mod detail;
+mod disk;
+mod export;
+mod files;
+mod filter;
+mod fuser;
+mod help;
+mod keys;
+mod net;
+mod priority;
+mod process;
+mod report;
+mod signal;
+mod smaps;
+mod summary;
+mod theme;
+mod threads;
+mod tree;
 
+use std::{
+    collections::HashSet,
+    error::Error,
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use cli::Options;
+use column::Column;
+use config::Config;
+use detail::Details;
+use export::Format;
+use files::OpenFile;
+use filter::{Field, Filter};
+use fuser::Holder;
+use keys::{Action, Keymap};
+use net::Socket;
+use priority::{Setting, Settings};
+use process::{ProcessMap, RowState};
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     crossterm::{
-        event::{
-            self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind,
-        },
+        event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
         execute,
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     },
     layout::{Constraint, Layout, Margin, Rect},
-    style::{self, Color, Modifier, Style, Stylize},
+    style::{Modifier, Style, Stylize},
     terminal::{Frame, Terminal},
     text::Line,
     widgets::{
-        Block, BorderType, Cell, List, ListState, Paragraph, Row, Scrollbar,
-        ScrollbarOrientation, ScrollbarState, Table, TableState,
+        Block, BorderType, Cell, List, ListState, Paragraph, Row, Scrollbar, ScrollbarOrientation,
+        ScrollbarState, Table, TableState,
     },
 };
-use style::palette::tailwind;
-use users::{get_current_uid, get_user_by_uid};
-
-const PALETTES: [tailwind::Palette; 4] = [
-    tailwind::BLUE,
-    tailwind::EMERALD,
-    tailwind::INDIGO,
-    tailwind::RED,
-];
-const INFO_TEXT: &str =
-    "(Esc) quit | (↑) move up | (↓) move down | (f) file filter | (u) user filter | (c) to clear";
+use report::Outcome;
+use signal::SignalTarget;
+use smaps::Mapping;
+use summary::Summary;
+use sysinfo::{ProcessRefreshKind, System, MINIMUM_CPU_UPDATE_INTERVAL};
+use theme::{TableColors, Theme};
+use unicode_width::UnicodeWidthStr;
 
 const ITEM_HEIGHT: usize = 4;
 
-struct TableColors {
-    buffer_bg: Color,
-    header_bg: Color,
-    header_fg: Color,
-    row_fg: Color,
-    selected_style_fg: Color,
-    normal_row_color: Color,
-    alt_row_color: Color,
-    footer_border_color: Color,
-}
-
-impl TableColors {
-    const fn new(color: &tailwind::Palette) -> Self {
-        Self {
-            buffer_bg: tailwind::SLATE.c950,
-            header_bg: color.c900,
-            header_fg: tailwind::SLATE.c200,
-            row_fg: tailwind::SLATE.c200,
-            selected_style_fg: color.c400,
-            normal_row_color: tailwind::SLATE.c950,
-            alt_row_color: tailwind::SLATE.c900,
-            footer_border_color: color.c400,
-        }
-    }
-}
-
-struct ProcessMap {
-    pid: u32,
-    name: String,
-    path: String,
-    user: String,
-    // memory: u64,
-}
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_millis(2000);
+const MAX_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const REFRESH_STEP: Duration = Duration::from_millis(500);
 
 struct App {
     state: TableState,
-    items: [Vec<String>; 4],
+    /// Text of every column of each row, indexed by `Column::index`
+    items: Vec<Vec<String>>,
+    processes: Vec<ProcessMap>,
+    rows: Vec<ProcessMap>,
+    inaccessible: usize,
+    summary: Summary,
+    sys: System,
+    refresh_interval: Duration,
+    last_refresh: Instant,
+    /// Time between the last two process samples, which disk I/O rates are
+    /// computed over
+    io_interval: Duration,
+    selected_pid: Option<u32>,
+    visible_pids: Vec<u32>,
+    sort_column: Column,
+    sort_descending: bool,
+    /// Visible columns, in display order
+    columns: Vec<Column>,
+    /// Widths set by the user, by `Column::index`, the others fit their content
+    widths: [Option<u16>; Column::ALL.len()],
+    /// Widths of the visible columns as last drawn
+    shown_widths: Vec<u16>,
+    column_state: ListState,
+    keymap: Keymap,
+    /// The config file as loaded, updated when the layout is saved
+    config: Config,
+    config_path: Option<PathBuf>,
+    tree_mode: bool,
+    /// List threads along with processes
+    show_threads: bool,
+    /// Process whose threads the thread list shows
+    thread_pid: u32,
+    thread_rows: Vec<ProcessMap>,
+    thread_state: TableState,
+    /// Process whose descriptors the open files panel shows
+    files_pid: u32,
+    files_limit: Option<u64>,
+    open_files: Vec<OpenFile>,
+    files_state: TableState,
+    /// Process whose sockets the socket panel shows
+    sockets_pid: u32,
+    sockets: Vec<Socket>,
+    sockets_state: TableState,
+    /// Process whose mappings the memory map shows
+    mappings_pid: u32,
+    mappings: Vec<Mapping>,
+    mappings_state: TableState,
+    /// Show one row per backing file rather than per mapping
+    merge_mappings: bool,
+    /// Path typed in the file users prompt
+    fuser_input: String,
+    /// Path the file users list was found for, empty for deleted files
+    fuser_path: PathBuf,
+    holders: Vec<Holder>,
+    holders_state: TableState,
+    fuser_unreadable: usize,
+    collapsed: HashSet<u32>,
+    has_children: HashSet<u32>,
+    /// Processes tagged for the signal, priority, affinity and export actions
+    tagged: HashSet<u32>,
+    signal_state: ListState,
+    signal_target: SignalTarget,
+    signal_input: String,
+    pending_signal: Option<i32>,
+    /// Processes the signal menu sends to
+    signal_pids: Vec<u32>,
+    /// Processes the priority dialog applies to, each with whether it is a thread
+    priority_targets: Vec<(u32, bool)>,
+    /// Settings of the process when the dialog was opened, and as edited
+    priority_old: Settings,
+    priority_new: Settings,
+    priority_state: ListState,
+    /// Processes the affinity editor applies to, each with whether it is a thread
+    affinity_targets: Vec<(u32, bool)>,
+    /// Whether each CPU is checked in the affinity editor
+    affinity_cpus: Vec<bool>,
+    affinity_cursor: usize,
+    /// Apply the affinity to every thread rather than the main one
+    affinity_all_threads: bool,
+    /// Outcome by process of the last action applied to several of them
+    report: Vec<Outcome>,
+    report_summary: String,
+    report_state: TableState,
+    help_state: TableState,
+    status: String,
+    export_path: String,
+    /// The process shown in the inspector
+    details: Option<Details>,
+    detail_scroll: u16,
+    /// Lines the inspector took once wrapped, when last drawn
+    detail_lines: usize,
     input: String,
-    longest_item_lens: (u16, u16, u16, u16), // order is (pid, name, path,, user, memory)
     scroll_state: ScrollbarState,
     colors: TableColors,
+    themes: Vec<Theme>,
+    /// Index of the current theme in `themes`
     color_index: usize,
     input_mode: InputMode,
-    message: Vec<String>,
     character_index: usize,
     file_or_user: String,
-    message_state: ListState,
+    filters: Vec<Filter>,
+    filter_error: Option<String>,
     filtered_rows: usize,
 }
 
 impl App {
-    fn new() -> Self {
-        use sysinfo::{Components, Disks, Networks, System};
-
+    fn new(options: &Options, config: Config, config_path: Option<PathBuf>) -> Self {
         // "new_all" to ensure that all list;/mof components, network interfaces,
         // disks and users are already filled!
         let mut sys = System::new_all();
+        let first_sample = Instant::now();
 
-        let user = get_user_by_uid(get_current_uid()).unwrap();
-
-        // Update all information of our `System` struct.
-        sys.refresh_all();
-
-        // let mut user_uid: String = String::new();
+        // CPU usage is computed between two refreshes, so take a second sample
+        // before the first frame rather than showing 0% everywhere
+        std::thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+        sys.refresh_processes_specifics(ProcessRefreshKind::everything());
+        sys.refresh_cpu_usage();
+        let second_sample = Instant::now();
 
-        let mut table_process_map = vec![];
+        let mut themes = theme::builtin();
+        themes.extend(options.themes.iter().cloned());
+        // the theme name was checked in `main`
+        let color_index = match &options.palette {
+            Some(name) => theme::find(&themes, name),
+            None if theme::no_color() => theme::find(&themes, theme::MONOCHROME),
+            None => None,
+        }
+        .unwrap_or(0);
 
-        for (pid, process) in sys.processes() {
-            // std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
-            let user_uid = get_user_by_uid(
-                process
-                    .user_id()
-                    .unwrap()
-                    .to_string()
-                    .parse::<u32>()
-                    .unwrap(),
-            )
-            .unwrap()
-            .name()
-            .to_os_string()
-            .as_os_str()
-            .to_str()
-            .unwrap()
-            .to_string();
-            table_process_map.push(ProcessMap {
-                pid: pid.as_u32(),
-                name: String::from(process.name()),
-                path: String::from(process.exe().unwrap().to_str().unwrap()),
-                user: user_uid,
-                // memory: process.memory(),
-            });
-        }
-
-        table_process_map.sort_by_key(|element: &ProcessMap| element.pid.clone());
-
-        let mut pids = vec![];
-        let mut names = vec![];
-        let mut path = vec![];
-        let mut users = vec![];
-
-        for element in table_process_map {
-            pids.push(element.pid.to_string());
-            names.push(element.name);
-            path.push(element.path);
-            users.push(element.user);
-        }
-
-        let data_vec = [pids, names, path, users];
-        let data_vec_len = data_vec[0].len();
-        Self {
+        let mut app = Self {
             state: TableState::default().with_selected(0),
-            longest_item_lens: (100, 100, 100, 100),
-            scroll_state: ScrollbarState::new((data_vec[0].len() - 1) * ITEM_HEIGHT),
-            colors: TableColors::new(&PALETTES[0]),
-            color_index: 0,
-            items: data_vec,
+            items: Vec::new(),
+            processes: Vec::new(),
+            rows: Vec::new(),
+            inaccessible: 0,
+            summary: Summary::new(),
+            sys,
+            refresh_interval: options.delay.unwrap_or(DEFAULT_REFRESH_INTERVAL),
+            last_refresh: second_sample,
+            io_interval: second_sample - first_sample,
+            selected_pid: None,
+            visible_pids: Vec::new(),
+            sort_column: options.sort.unwrap_or(Column::Pid),
+            sort_descending: options
+                .descending
+                .unwrap_or(options.sort.is_some_and(Column::default_descending))
+                != options.reverse,
+            columns: options
+                .columns
+                .clone()
+                .unwrap_or_else(|| Column::DEFAULT.to_vec()),
+            widths: [None; Column::ALL.len()],
+            shown_widths: Vec::new(),
+            column_state: ListState::default(),
+            keymap: Keymap::new(&options.keys),
+            config,
+            config_path,
+            tree_mode: options.tree.unwrap_or(false),
+            show_threads: options.threads.unwrap_or(false),
+            thread_pid: 0,
+            thread_rows: Vec::new(),
+            thread_state: TableState::default(),
+            files_pid: 0,
+            files_limit: None,
+            open_files: Vec::new(),
+            files_state: TableState::default(),
+            sockets_pid: 0,
+            sockets: Vec::new(),
+            sockets_state: TableState::default(),
+            mappings_pid: 0,
+            mappings: Vec::new(),
+            mappings_state: TableState::default(),
+            merge_mappings: false,
+            fuser_input: String::new(),
+            fuser_path: PathBuf::new(),
+            holders: Vec::new(),
+            holders_state: TableState::default(),
+            fuser_unreadable: 0,
+            collapsed: HashSet::new(),
+            has_children: HashSet::new(),
+            tagged: HashSet::new(),
+            signal_state: ListState::default(),
+            signal_target: SignalTarget::Process,
+            signal_input: String::new(),
+            pending_signal: None,
+            signal_pids: Vec::new(),
+            priority_targets: Vec::new(),
+            priority_old: Settings::default(),
+            priority_new: Settings::default(),
+            priority_state: ListState::default(),
+            affinity_targets: Vec::new(),
+            affinity_cpus: Vec::new(),
+            affinity_cursor: 0,
+            affinity_all_threads: true,
+            report: Vec::new(),
+            report_summary: String::new(),
+            report_state: TableState::default(),
+            help_state: TableState::default(),
+            status: String::new(),
+            export_path: String::new(),
+            details: None,
+            detail_scroll: 0,
+            detail_lines: 0,
+            scroll_state: ScrollbarState::new(0),
+            colors: themes[color_index].colors,
+            themes,
+            color_index,
             input_mode: InputMode::Normal,
             input: String::new(),
-            message: Vec::new(),
             character_index: 0,
             file_or_user: String::new(),
-            message_state: ListState::default(),
-            filtered_rows: data_vec_len,
+            filters: Vec::new(),
+            filter_error: None,
+            filtered_rows: 0,
+        };
+        for &(column, width) in &options.widths {
+            app.widths[column.index()] = Some(width);
+        }
+        for (query, column) in &options.filters {
+            // already validated when parsing the command line
+            if let Ok(filter) = Filter::parse(query, *column) {
+                app.filters.push(filter);
+            }
+        }
+        // build the first rows from the second sample, refreshing again now
+        // would measure CPU usage over next to no time
+        app.summary.update(&app.sys);
+        app.collect();
+        app
+    }
+
+    /// Indices into `rows` and `items` of the rows that pass every filter.
+    fn visible_rows(&self) -> Vec<usize> {
+        (0..self.rows.len())
+            .filter(|&n| {
+                self.filters
+                    .iter()
+                    .all(|filter| filter.matches(&self.rows[n], &self.items[n]))
+            })
+            .collect()
+    }
+
+    /// Re-read the process list from the system and rebuild the table columns.
+    pub fn refresh(&mut self) {
+        self.sys
+            .refresh_processes_specifics(ProcessRefreshKind::everything());
+        let sampled = Instant::now();
+        self.io_interval = sampled - self.last_refresh;
+        self.last_refresh = sampled;
+        self.sys.refresh_memory();
+        self.sys.refresh_cpu_usage();
+        self.summary.update(&self.sys);
+        self.collect();
+
+        if let InputMode::Threads = self.input_mode {
+            if let Some(rows) = threads::list(&self.sys, self.thread_pid, self.io_interval) {
+                self.thread_rows = rows;
+            }
+        }
+        if let InputMode::Files = self.input_mode {
+            if let Ok(files) = files::list(self.files_pid) {
+                self.open_files = files;
+            }
+        }
+        if let InputMode::MemoryMap = self.input_mode {
+            if let Ok(mappings) = smaps::read(self.mappings_pid) {
+                self.mappings = mappings;
+            }
+        }
+        if let InputMode::Sockets = self.input_mode {
+            if let Ok(sockets) = net::list(self.sockets_pid) {
+                self.sockets = sockets;
+            }
+        }
+
+        // keep the inspector live, showing the last details once the process exits
+        if let InputMode::Details = self.input_mode {
+            if let Some(pid) = self.details.as_ref().map(|details| details.pid) {
+                if let Some(details) = detail::inspect(&self.sys, pid) {
+                    self.details = Some(details);
+                }
+            }
+        }
+    }
+
+    /// Rebuild the rows from what `sys` read on the last refresh.
+    fn collect(&mut self) {
+        let snapshot = process::collect(
+            &self.sys,
+            self.show_threads,
+            self.io_interval,
+            &self.wanted_columns(),
+        );
+        let mut table_process_map = snapshot.processes;
+        process::mark_changes(&self.processes, &mut table_process_map);
+        self.processes = table_process_map;
+        self.inaccessible = snapshot.inaccessible;
+
+        // forget collapsed processes that have exited, their PID may be reused
+        let known: HashSet<u32> = self.processes.iter().map(|p| p.pid).collect();
+        self.collapsed.retain(|pid| known.contains(pid));
+        let running: HashSet<u32> = self
+            .processes
+            .iter()
+            .filter(|p| p.row_state != RowState::Removed)
+            .map(|p| p.pid)
+            .collect();
+        self.tagged.retain(|pid| running.contains(pid));
+
+        self.find_listeners();
+        self.read_rollups();
+        self.sort_items();
+    }
+
+    /// The columns shown or sorted on, the only ones whose figures are read
+    /// when reading them takes work of its own.
+    fn wanted_columns(&self) -> Vec<Column> {
+        let mut wanted = self.columns.clone();
+        wanted.push(self.sort_column);
+        wanted
+    }
+
+    /// Read the proportional and unique set sizes, if a column shows them.
+    fn read_rollups(&mut self) {
+        let wanted = self.wanted_columns();
+        if !wanted.contains(&Column::Pss) && !wanted.contains(&Column::Uss) {
+            return;
+        }
+        for process in self.processes.iter_mut().filter(|p| !p.thread) {
+            if process.pss.is_some() {
+                continue;
+            }
+            if let Some(usage) = smaps::rollup(process.pid) {
+                process.pss = Some(usage.pss);
+                process.uss = Some(usage.uss());
+            }
         }
     }
+
+    /// Look up the ports each process listens on, if a filter needs them.
+    fn find_listeners(&mut self) {
+        if !self.filters.iter().any(Filter::uses_ports) {
+            return;
+        }
+        let listeners = net::listeners();
+        for process in &mut self.processes {
+            process.listening = listeners.get(&process.pid).cloned().unwrap_or_default();
+        }
+    }
+
+    /// Sort the collected processes by the current sort column, arrange them as
+    /// a tree if tree mode is on, and rebuild the table columns from them.
+    fn sort_items(&mut self) {
+        process::sort(&mut self.processes, self.sort_column, self.sort_descending);
+
+        let mut prefixes = vec![];
+        self.has_children.clear();
+        if self.tree_mode {
+            self.rows = vec![];
+            for row in tree::flatten(&self.processes, &self.collapsed) {
+                if row.has_children {
+                    self.has_children.insert(row.process.pid);
+                }
+                prefixes.push(row.prefix);
+                self.rows.push(row.process);
+            }
+        } else {
+            self.rows = self.processes.clone();
+        }
+
+        self.items = self
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(n, element)| {
+                let mut cells: Vec<String> = Column::ALL
+                    .iter()
+                    .map(|column| column.cell(element))
+                    .collect();
+                if let Some(prefix) = prefixes.get(n) {
+                    cells[Column::Name.index()].insert_str(0, prefix);
+                }
+                cells
+            })
+            .collect();
+    }
+
+    /// Sort by the next visible column, using that column's natural direction.
+    pub fn cycle_sort(&mut self) {
+        let next = self
+            .columns
+            .iter()
+            .position(|&column| column == self.sort_column)
+            .map_or(0, |i| (i + 1) % self.columns.len());
+        self.sort_column = self.columns[next];
+        self.sort_descending = self.sort_column.default_descending();
+        self.sort_items();
+    }
+
+    pub fn reverse_sort(&mut self) {
+        self.sort_descending = !self.sort_descending;
+        self.sort_items();
+    }
+
+    pub fn toggle_tree(&mut self) {
+        self.tree_mode = !self.tree_mode;
+        self.sort_items();
+    }
+
+    /// List threads along with processes, or stop listing them.
+    pub fn toggle_threads(&mut self) {
+        self.show_threads = !self.show_threads;
+        self.collect();
+    }
+
+    /// Open the thread list of the selected process, or of the process the
+    /// selected thread belongs to.
+    pub fn thread_list(&mut self) {
+        let Some(pid) = self.selected_pid else {
+            return;
+        };
+        let pid = match self.rows.iter().find(|row| row.pid == pid) {
+            Some(row) if row.thread => row.parent.unwrap_or(pid),
+            _ => pid,
+        };
+        match threads::list(&self.sys, pid, self.io_interval) {
+            Some(rows) => {
+                self.thread_pid = pid;
+                self.thread_rows = rows;
+                self.thread_state.select(Some(0));
+                self.input_mode = InputMode::Threads;
+            }
+            None => self.status = format!("Process {pid} has exited"),
+        }
+    }
+
+    /// Open the list of file descriptors of the selected process.
+    pub fn open_files(&mut self) {
+        let Some(pid) = self.selected_pid else {
+            return;
+        };
+        // threads share the descriptors of their process
+        let pid = match self.rows.iter().find(|row| row.pid == pid) {
+            Some(row) if row.thread => row.parent.unwrap_or(pid),
+            _ => pid,
+        };
+        match files::list(pid) {
+            Ok(files) => {
+                self.files_pid = pid;
+                self.files_limit = files::limit(pid);
+                self.open_files = files;
+                self.files_state.select(Some(0));
+                self.input_mode = InputMode::Files;
+            }
+            Err(err) => self.status = format!("Cannot list the open files of process {pid}: {err}"),
+        }
+    }
+
+    /// Open the list of sockets of the selected process.
+    pub fn sockets(&mut self) {
+        let Some(pid) = self.selected_pid else {
+            return;
+        };
+        // threads share the descriptors of their process
+        let pid = match self.rows.iter().find(|row| row.pid == pid) {
+            Some(row) if row.thread => row.parent.unwrap_or(pid),
+            _ => pid,
+        };
+        match net::list(pid) {
+            Ok(sockets) => {
+                self.sockets_pid = pid;
+                self.sockets = sockets;
+                self.sockets_state.select(Some(0));
+                self.input_mode = InputMode::Sockets;
+            }
+            Err(err) => self.status = format!("Cannot list the sockets of process {pid}: {err}"),
+        }
+    }
+
+    /// Open the memory map of the selected process.
+    pub fn memory_map(&mut self) {
+        let Some(pid) = self.selected_pid else {
+            return;
+        };
+        // threads share the address space of their process
+        let pid = match self.rows.iter().find(|row| row.pid == pid) {
+            Some(row) if row.thread => row.parent.unwrap_or(pid),
+            _ => pid,
+        };
+        match smaps::read(pid) {
+            Ok(mappings) => {
+                self.mappings_pid = pid;
+                self.mappings = mappings;
+                self.mappings_state.select(Some(0));
+                self.input_mode = InputMode::MemoryMap;
+            }
+            Err(err) => self.status = format!("Cannot read the memory map of process {pid}: {err}"),
+        }
+    }
+
+    pub fn fuser_prompt(&mut self) {
+        self.input_mode = InputMode::FindPath;
+    }
+
+    /// List the processes using the path typed in the prompt, or holding
+    /// deleted files if it is empty.
+    pub fn find_holders(&mut self) {
+        self.fuser_path = match self.fuser_input.trim().is_empty() {
+            true => PathBuf::new(),
+            false => fuser::resolve(&self.fuser_input),
+        };
+        let path = Some(self.fuser_path.as_path()).filter(|path| !path.as_os_str().is_empty());
+        let lookup = fuser::find(&self.processes, path);
+        self.holders = lookup.holders;
+        self.fuser_unreadable = lookup.unreadable;
+        self.holders_state.select(Some(0));
+        self.input_mode = InputMode::Holders;
+    }
+
+    /// Close the file users list and select the chosen process in the table.
+    pub fn select_holder(&mut self) {
+        let Some(holder) = self
+            .holders_state
+            .selected()
+            .and_then(|i| self.holders.get(i))
+        else {
+            return;
+        };
+        match self.visible_pids.iter().position(|&pid| pid == holder.pid) {
+            Some(i) => self.select_row(i),
+            None => self.status = format!("Process {} is not shown in the table", holder.pid),
+        }
+        self.input_mode = InputMode::Normal;
+    }
+
+    /// Collapse the subtree of the selected process. On a leaf or an already
+    /// collapsed process, move the selection to its parent instead.
+    pub fn collapse(&mut self) {
+        if !self.tree_mode {
+            return;
+        }
+        let Some(pid) = self.selected_pid else {
+            return;
+        };
+        if self.has_children.contains(&pid) && !self.collapsed.contains(&pid) {
+            self.collapsed.insert(pid);
+            self.sort_items();
+        } else if let Some(parent) = self
+            .rows
+            .iter()
+            .find(|p| p.pid == pid)
+            .and_then(|p| p.parent)
+        {
+            if self.visible_pids.contains(&parent) {
+                self.selected_pid = Some(parent);
+            }
+        }
+    }
+
+    /// Expand the subtree of the selected process.
+    pub fn expand(&mut self) {
+        if !self.tree_mode {
+            return;
+        }
+        if let Some(pid) = self.selected_pid {
+            if self.collapsed.remove(&pid) {
+                self.sort_items();
+            }
+        }
+    }
+
+    /// Open the signal menu for the tagged processes, or the selected one,
+    /// leaving out those that have exited: their PID may already be reused.
+    pub fn signal_menu(&mut self) {
+        let targets = self.targets();
+        self.signal_pids = targets
+            .iter()
+            .map(|&(pid, _)| pid)
+            .filter(|&pid| !signal::exited(&self.processes, pid))
+            .collect();
+        if self.signal_pids.is_empty() {
+            if let [(pid, _)] = targets.as_slice() {
+                self.status = format!("Process {pid} has exited");
+            } else if !targets.is_empty() {
+                self.status = String::from("The tagged processes have exited");
+            }
+            return;
+        }
+        self.signal_state.select(Some(0));
+        self.signal_target = SignalTarget::Process;
+        self.input_mode = InputMode::SignalMenu;
+    }
+
+    /// Pick the highlighted menu entry, either asking for confirmation or, for
+    /// the last entry, for a signal number.
+    pub fn choose_signal(&mut self) {
+        match self.signal_state.selected() {
+            Some(i) if i < signal::SIGNALS.len() => {
+                self.pending_signal = Some(signal::SIGNALS[i].1);
+                self.input_mode = InputMode::ConfirmSignal;
+            }
+            _ => {
+                self.signal_input.clear();
+                self.input_mode = InputMode::SignalNumber;
+            }
+        }
+    }
+
+    pub fn submit_signal_number(&mut self) {
+        match signal::parse_signal(&self.signal_input) {
+            Some(number) => {
+                self.pending_signal = Some(number);
+                self.input_mode = InputMode::ConfirmSignal;
+            }
+            None => {
+                self.status = format!("Unknown signal: {}", self.signal_input);
+                self.input_mode = InputMode::Normal;
+            }
+        }
+    }
+
+    pub fn confirm_signal(&mut self) {
+        let pending = self.pending_signal;
+        self.cancel_signal();
+        if let Some(number) = pending {
+            let results = signal::deliver(
+                &self.processes,
+                &self.signal_pids,
+                number,
+                self.signal_target,
+            );
+            let summary = signal::summary(number, self.signal_target, &results);
+            self.show_outcome(summary, results);
+        }
+    }
+
+    pub fn cancel_signal(&mut self) {
+        self.pending_signal = None;
+        self.input_mode = InputMode::Normal;
+    }
+
+    /// Open the priority dialog on the tagged processes, or the selected one,
+    /// with the current settings of the selected one.
+    pub fn priority_dialog(&mut self) {
+        let targets = self.targets();
+        let Some(pid) = self.baseline(&targets) else {
+            return;
+        };
+        match Settings::read(pid) {
+            Some(settings) => {
+                self.priority_targets = targets;
+                self.priority_old = settings;
+                self.priority_new = settings;
+                self.priority_state.select(Some(0));
+                self.input_mode = InputMode::Priority;
+            }
+            None => self.status = format!("Process {pid} has exited"),
+        }
+    }
+
+    /// Step the highlighted setting of the priority dialog.
+    pub fn adjust_priority(&mut self, step: i32) {
+        if let Some(&setting) = self
+            .priority_state
+            .selected()
+            .and_then(|i| Setting::ALL.get(i))
+        {
+            self.priority_new.adjust(setting, step);
+        }
+    }
+
+    pub fn apply_priority(&mut self) {
+        self.input_mode = InputMode::Normal;
+        if self.priority_old == self.priority_new {
+            self.status = String::from("Priority unchanged");
+            return;
+        }
+        let results = priority::apply(
+            &self.priority_targets,
+            &self.priority_old,
+            &self.priority_new,
+        );
+        self.show_outcome(priority::summary(&results), results);
+        self.reread_scheduling(&self.priority_targets.clone());
+    }
+
+    /// Read the scheduling settings of `targets` and of their threads again
+    /// after changing them. `sys` is left alone: sampling it out of turn would
+    /// measure CPU usage and disk rates over too short an interval.
+    fn reread_scheduling(&mut self, targets: &[(u32, bool)]) {
+        let changed = |process: &ProcessMap| {
+            targets.iter().any(|&(pid, thread)| {
+                process.pid == pid || (!thread && process.thread && process.parent == Some(pid))
+            })
+        };
+        for process in self
+            .processes
+            .iter_mut()
+            .filter(|process| process.row_state != RowState::Removed && changed(process))
+        {
+            process::reread_scheduling(process);
+        }
+        self.sort_items();
+    }
+
+    /// Open the affinity editor on the tagged processes, or the selected one,
+    /// with the CPUs of the selected one checked.
+    pub fn affinity_editor(&mut self) {
+        let targets = self.targets();
+        let Some(pid) = self.baseline(&targets) else {
+            return;
+        };
+        match affinity::get(pid) {
+            Ok(cpus) => {
+                let count = affinity::cpu_count();
+                self.affinity_cpus = (0..count).map(|cpu| cpus.contains(&cpu)).collect();
+                self.affinity_targets = targets;
+                self.affinity_cursor = 0;
+                self.input_mode = InputMode::Affinity;
+            }
+            Err(err) => self.status = format!("Cannot read the affinity of {pid}: {err}"),
+        }
+    }
+
+    pub fn toggle_cpu(&mut self) {
+        if let Some(checked) = self.affinity_cpus.get_mut(self.affinity_cursor) {
+            *checked = !*checked;
+        }
+    }
+
+    /// Check every CPU, or only the one under the cursor if all already are.
+    pub fn check_all_cpus(&mut self) {
+        let all = self.affinity_cpus.iter().all(|&checked| checked);
+        for (cpu, checked) in self.affinity_cpus.iter_mut().enumerate() {
+            *checked = !all || cpu == self.affinity_cursor;
+        }
+    }
+
+    pub fn apply_affinity(&mut self) {
+        let cpus = affinity::allowed(&self.affinity_cpus);
+        if cpus.is_empty() {
+            self.status = String::from("Check at least one CPU");
+            return;
+        }
+        self.input_mode = InputMode::Normal;
+        let results = affinity::apply(&self.affinity_targets, &cpus, self.affinity_all_threads);
+        self.show_outcome(affinity::summary(&cpus, &results), results);
+        self.reread_scheduling(&self.affinity_targets.clone());
+    }
+
+    /// The processes an action applies to, each with whether it is a thread:
+    /// the tagged ones if any, otherwise the selected one.
+    fn targets(&self) -> Vec<(u32, bool)> {
+        match self.tagged.is_empty() {
+            true => self
+                .selected_pid
+                .map(|pid| {
+                    let thread = self.rows.iter().any(|row| row.pid == pid && row.thread);
+                    vec![(pid, thread)]
+                })
+                .unwrap_or_default(),
+            false => self
+                .processes
+                .iter()
+                .filter(|process| self.tagged.contains(&process.pid))
+                .map(|process| (process.pid, process.thread))
+                .collect(),
+        }
+    }
+
+    /// The target whose current settings a dialog starts from: the selected
+    /// process if it is one of them.
+    fn baseline(&self, targets: &[(u32, bool)]) -> Option<u32> {
+        targets
+            .iter()
+            .map(|&(pid, _)| pid)
+            .find(|&pid| Some(pid) == self.selected_pid)
+            .or(targets.first().map(|&(pid, _)| pid))
+    }
+
+    /// Show the outcome of an action in the status line and, if it applied to
+    /// several processes, process by process.
+    fn show_outcome(&mut self, summary: String, results: Vec<(u32, io::Result<()>)>) {
+        if results.len() > 1 {
+            self.report = report::outcomes(&self.processes, results);
+            self.report_summary = summary.clone();
+            self.report_state.select(Some(0));
+            self.input_mode = InputMode::Report;
+        }
+        self.status = summary;
+    }
+
+    /// Tag or untag the selected process and move to the next row.
+    pub fn toggle_tag(&mut self) {
+        let Some(pid) = self.selected_pid else {
+            return;
+        };
+        if !self.tagged.remove(&pid) {
+            self.tagged.insert(pid);
+        }
+        if let Some(i) = self.state.selected() {
+            self.select_row((i + 1).min(self.filtered_rows.saturating_sub(1)));
+        }
+    }
+
+    /// Tag every process the filters let through.
+    pub fn tag_matching(&mut self) {
+        self.tagged.extend(self.visible_pids.iter().copied());
+        self.status = format!("{} processes tagged", self.tagged.len());
+    }
+
+    /// Tag the untagged processes the filters let through and untag the
+    /// others, leaving those filtered out as they are.
+    pub fn invert_tags(&mut self) {
+        for pid in &self.visible_pids {
+            if !self.tagged.remove(pid) {
+                self.tagged.insert(*pid);
+            }
+        }
+        self.status = format!("{} processes tagged", self.tagged.len());
+    }
+
+    pub fn untag_all(&mut self) {
+        self.tagged.clear();
+        self.status = String::from("No process tagged");
+    }
+
+    /// Open the inspector on the selected process.
+    pub fn inspect(&mut self) {
+        let Some(pid) = self.selected_pid else {
+            return;
+        };
+        match detail::inspect(&self.sys, pid) {
+            Some(details) => {
+                self.details = Some(details);
+                self.detail_scroll = 0;
+                self.input_mode = InputMode::Details;
+            }
+            None => self.status = format!("Process {pid} has exited"),
+        }
+    }
+
+    /// Scroll the inspector by `lines`, up if negative.
+    pub fn scroll_details(&mut self, lines: isize) {
+        self.detail_scroll = (self.detail_scroll as usize)
+            .saturating_add_signed(lines)
+            .min(self.detail_lines.saturating_sub(1))
+            .try_into()
+            .unwrap_or(u16::MAX);
+    }
+
+    /// Copy the whole inspector, or only the command line, to the clipboard.
+    pub fn copy_details(&mut self, everything: bool) {
+        let Some(details) = &self.details else {
+            return;
+        };
+        let (text, what) = if everything {
+            (details.text(), "details")
+        } else {
+            (details.command.clone(), "command line")
+        };
+        self.status = match clipboard::copy(&text) {
+            Ok(()) => format!(
+                "Copied the {what} of process {} to the clipboard",
+                details.pid
+            ),
+            Err(err) => format!("Failed to copy to the clipboard: {err}"),
+        };
+    }
+
+    pub fn export_prompt(&mut self) {
+        if self.export_path.is_empty() {
+            self.export_path = String::from("rustytop.json");
+        }
+        self.input_mode = InputMode::Export;
+    }
+
+    /// Write the rows currently shown, filtered and in display order, to the
+    /// file named in the export prompt. Only the tagged ones if there are any.
+    pub fn export(&mut self) {
+        let rows = self.visible_rows();
+        let processes: Vec<_> = rows
+            .iter()
+            .map(|&n| &self.rows[n])
+            .filter(|row| self.tagged.is_empty() || self.tagged.contains(&row.pid))
+            .collect();
+        let path = std::path::Path::new(&self.export_path);
+        self.status = match export::export_file(path, &processes) {
+            Ok(()) => format!(
+                "Exported {} {}processes to {}",
+                processes.len(),
+                if self.tagged.is_empty() {
+                    ""
+                } else {
+                    "tagged "
+                },
+                path.display()
+            ),
+            Err(err) => format!("Failed to export to {}: {err}", path.display()),
+        };
+        self.input_mode = InputMode::Normal;
+    }
+
+    pub fn show_help(&mut self) {
+        self.help_state.select(Some(0));
+        self.input_mode = InputMode::Help;
+    }
+
+    /// Save the columns, widths, sort order, refresh interval, palette and tree
+    /// mode to the config file, keeping the key bindings it already had.
+    pub fn save_config(&mut self) {
+        let Some(path) = self.config_path.clone() else {
+            self.status =
+                String::from("Cannot save the layout: neither XDG_CONFIG_HOME nor HOME is set");
+            return;
+        };
+        self.config.refresh_interval = Some(self.refresh_interval.as_secs_f64());
+        // under NO_COLOR the monochrome theme stands in for the configured one
+        let theme = &self.themes[self.color_index].name;
+        if !(theme::no_color() && *theme == theme::MONOCHROME) {
+            self.config.palette = Some(theme.clone());
+        }
+        self.config.sort = Some(self.sort_column.name().to_string());
+        self.config.sort_descending = Some(self.sort_descending);
+        self.config.tree = Some(self.tree_mode);
+        self.config.threads = Some(self.show_threads);
+        self.config.columns = Some(
+            self.columns
+                .iter()
+                .map(|column| column.name().to_string())
+                .collect(),
+        );
+        self.config.widths = Column::ALL
+            .iter()
+            .filter_map(|column| {
+                let width = self.widths[column.index()]?;
+                Some((column.name().to_string(), width))
+            })
+            .collect();
+        self.status = match self.config.save(&path) {
+            Ok(()) => format!("Saved layout to {}", path.display()),
+            Err(err) => format!("Failed to save layout to {}: {err}", path.display()),
+        };
+    }
+
+    /// Columns in the order the column editor lists them: the visible ones in
+    /// display order, then the hidden ones.
+    fn editor_columns(&self) -> Vec<Column> {
+        let mut columns = self.columns.clone();
+        columns.extend(
+            Column::ALL
+                .iter()
+                .filter(|column| !self.columns.contains(column)),
+        );
+        columns
+    }
+
+    /// The column highlighted in the column editor.
+    fn edited_column(&self) -> Option<Column> {
+        self.column_state
+            .selected()
+            .and_then(|i| self.editor_columns().get(i).copied())
+    }
+
+    pub fn column_editor(&mut self) {
+        self.column_state.select(Some(0));
+        self.input_mode = InputMode::Columns;
+    }
+
+    /// Show or hide the highlighted column. A column shown again goes after
+    /// the other visible ones, and the last visible column cannot be hidden.
+    pub fn toggle_column(&mut self) {
+        let Some(column) = self.edited_column() else {
+            return;
+        };
+        match self.columns.iter().position(|&c| c == column) {
+            Some(i) if self.columns.len() > 1 => {
+                self.columns.remove(i);
+            }
+            Some(_) => {}
+            None => self.columns.push(column),
+        }
+        // keep the cursor on the column, which may have moved in the list
+        let i = self.editor_columns().iter().position(|&c| c == column);
+        self.column_state.select(i);
+    }
+
+    /// Move the highlighted column `offset` places to the right, or to the
+    /// left if negative. Hidden columns have no place to move.
+    pub fn move_column(&mut self, offset: isize) {
+        let Some(column) = self.edited_column() else {
+            return;
+        };
+        let Some(i) = self.columns.iter().position(|&c| c == column) else {
+            return;
+        };
+        let j = i.saturating_add_signed(offset).min(self.columns.len() - 1);
+        let column = self.columns.remove(i);
+        self.columns.insert(j, column);
+        self.column_state.select(Some(j));
+    }
+
+    /// Widen or narrow the highlighted column. A column that was fitting its
+    /// content starts from the width it was last drawn with.
+    pub fn resize_column(&mut self, delta: i16) {
+        let Some(column) = self.edited_column() else {
+            return;
+        };
+        let shown = self
+            .columns
+            .iter()
+            .position(|&c| c == column)
+            .and_then(|i| self.shown_widths.get(i).copied());
+        let width = self.widths[column.index()]
+            .or(shown)
+            .unwrap_or(column.min_width());
+        self.widths[column.index()] = Some(width.saturating_add_signed(delta).max(1));
+    }
+
+    /// Let the highlighted column fit its content again.
+    pub fn auto_width(&mut self) {
+        if let Some(column) = self.edited_column() {
+            self.widths[column.index()] = None;
+        }
+    }
+
+    /// Lengthen the refresh interval by one step.
+    pub fn slower(&mut self) {
+        self.refresh_interval = (self.refresh_interval + REFRESH_STEP).min(MAX_REFRESH_INTERVAL);
+    }
+
+    /// Shorten the refresh interval by one step, never going below what sysinfo
+    /// needs between two refreshes to compute CPU usage.
+    pub fn faster(&mut self) {
+        self.refresh_interval = self
+            .refresh_interval
+            .saturating_sub(REFRESH_STEP)
+            .max(REFRESH_STEP.max(MINIMUM_CPU_UPDATE_INTERVAL));
+    }
+
     pub fn next(&mut self) {
+        if self.filtered_rows == 0 {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.filtered_rows - 1 {
@@ -170,11 +1131,13 @@ impl App {
             }
             None => 0,
         };
-        self.state.select(Some(i));
-        self.scroll_state = self.scroll_state.position(i * ITEM_HEIGHT);
+        self.select_row(i);
     }
 
     pub fn previous(&mut self) {
+        if self.filtered_rows == 0 {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -185,19 +1148,58 @@ impl App {
             }
             None => 0,
         };
+        self.select_row(i);
+    }
+
+    /// Select a row of the visible table and remember which PID it shows, so the
+    /// selection follows the process when rows move between refreshes.
+    fn select_row(&mut self, i: usize) {
         self.state.select(Some(i));
+        self.selected_pid = self.visible_pids.get(i).copied();
         self.scroll_state = self.scroll_state.position(i * ITEM_HEIGHT);
     }
 
     pub fn set_colors(&mut self) {
-        self.colors = TableColors::new(&PALETTES[self.color_index]);
+        self.colors = self.themes[self.color_index].colors;
+    }
+
+    /// Switch to the next theme, wrapping around to the first.
+    pub fn cycle_palette(&mut self) {
+        self.color_index = (self.color_index + 1) % self.themes.len();
+        self.status = format!("Theme: {}", self.themes[self.color_index].name);
     }
 
     pub fn submit_message(&mut self) {
-        self.message.push(self.input.clone());
+        let column = match self.file_or_user.as_str() {
+            "user" => Some(Field::User),
+            "all" | "port" => None,
+            _ => Some(Field::Path),
+        };
+        // the port prompt takes just the number
+        if self.file_or_user == "port" && !self.input.trim().is_empty() {
+            self.input = format!("port={}", self.input.trim());
+        }
+        // each submitted filter narrows the ones already active, a filter that
+        // does not parse is reported and dropped
+        match Filter::parse(&self.input, column) {
+            _ if self.input.trim().is_empty() => {}
+            Ok(filter) => {
+                let ports = filter.uses_ports();
+                self.filters.push(filter);
+                self.filter_error = None;
+                if ports {
+                    self.find_listeners();
+                    self.sort_items();
+                }
+            }
+            Err(err) => self.filter_error = Some(err),
+        }
+
         self.input.clear();
+        self.character_index = 0;
         self.input_mode = InputMode::Normal;
         self.state.select(Some(0));
+        self.selected_pid = None;
         self.scroll_state = self.scroll_state.position(0);
     }
 
@@ -207,6 +1209,21 @@ impl App {
         self.character_index = self.character_index.saturating_add(1);
     }
 
+    pub fn delete_char(&mut self) {
+        if self.character_index == 0 {
+            return;
+        }
+        self.character_index -= 1;
+        let index = self.byte_index();
+        self.input.remove(index);
+    }
+
+    pub fn cancel_editing(&mut self) {
+        self.input.clear();
+        self.character_index = 0;
+        self.input_mode = InputMode::Normal;
+    }
+
     pub fn byte_index(&self) -> usize {
         self.input
             .char_indices()
@@ -217,7 +1234,8 @@ impl App {
 
     pub fn clear(&mut self) {
         self.input = String::new();
-        self.message = Vec::new();
+        self.filters = Vec::new();
+        self.filter_error = None;
     }
 
     pub fn file(&mut self) {
@@ -229,9 +1247,75 @@ impl App {
         self.input_mode = InputMode::Editing;
         self.file_or_user = String::from("user");
     }
+
+    pub fn all_columns(&mut self) {
+        self.input_mode = InputMode::Editing;
+        self.file_or_user = String::from("all");
+    }
+
+    /// Filter the processes listening on a port.
+    pub fn listening_port(&mut self) {
+        self.input_mode = InputMode::Editing;
+        self.file_or_user = String::from("port");
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("rustytop: {err}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    let mut options = options;
+    let config_path = options.config.clone().or_else(config::default_path);
+    let config = match &config_path {
+        Some(path) => Config::load(path).and_then(|config| {
+            config
+                .apply(&mut options)
+                .map_err(|err| format!("invalid config {}: {err}", path.display()))?;
+            Ok(config)
+        }),
+        None => Ok(Config::default()),
+    }
+    .and_then(|config| {
+        let mut themes = theme::builtin();
+        themes.extend(options.themes.iter().cloned());
+        match &options.palette {
+            Some(name) if theme::find(&themes, name).is_none() => {
+                let names: Vec<_> = themes.iter().map(|theme| theme.name.as_str()).collect();
+                Err(format!(
+                    "unknown theme '{name}', expected {}",
+                    names.join(", ")
+                ))
+            }
+            _ => Ok(config),
+        }
+    });
+    let config = match config {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("rustytop: {err}");
+            std::process::exit(2);
+        }
+    };
+
+    if options.batch {
+        let mut app = App::new(&options, config, config_path);
+        batch::run(
+            &mut app,
+            options.iterations.unwrap_or(1),
+            options.output.unwrap_or(Format::Table),
+        )?;
+        return Ok(());
+    }
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -240,7 +1324,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut terminal = Terminal::new(backend)?;
 
     // create app and run it
-    let app = App::new();
+    let app = App::new(&options, config, config_path);
     let res = run_app(&mut terminal, app);
 
     // restore terminal
@@ -262,28 +1346,285 @@ fn main() -> Result<(), Box<dyn Error>> {
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
     loop {
         terminal.draw(|f| ui(f, &mut app))?;
+
+        let timeout = app
+            .refresh_interval
+            .saturating_sub(app.last_refresh.elapsed());
+        if !event::poll(timeout)? {
+            app.refresh();
+            continue;
+        }
+
         if let Event::Key(key) = event::read()? {
             match app.input_mode {
                 InputMode::Normal => {
                     if key.kind == KeyEventKind::Press {
-                        match key.code {
-                            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
-                            KeyCode::Char('j') | KeyCode::Down => app.next(),
-                            KeyCode::Char('k') | KeyCode::Up => app.previous(),
-                            KeyCode::Char('f') | KeyCode::Insert => app.file(),
-                            KeyCode::Char('c') => app.clear(),
-                            KeyCode::Char('u') => app.user(),
-                            _ => {}
+                        match app.keymap.action(key.code) {
+                            Some(Action::Quit) => return Ok(()),
+                            Some(Action::Down) => app.next(),
+                            Some(Action::Up) => app.previous(),
+                            Some(Action::FilterFile) => app.file(),
+                            Some(Action::ClearFilters) => app.clear(),
+                            Some(Action::FilterUser) => app.user(),
+                            Some(Action::FilterAll) => app.all_columns(),
+                            Some(Action::Sort) => app.cycle_sort(),
+                            Some(Action::ReverseSort) => app.reverse_sort(),
+                            Some(Action::Tree) => app.toggle_tree(),
+                            Some(Action::Threads) => app.toggle_threads(),
+                            Some(Action::ThreadList) => app.thread_list(),
+                            Some(Action::OpenFiles) => app.open_files(),
+                            Some(Action::FileUsers) => app.fuser_prompt(),
+                            Some(Action::Sockets) => app.sockets(),
+                            Some(Action::ListeningPort) => app.listening_port(),
+                            Some(Action::MemoryMap) => app.memory_map(),
+                            Some(Action::Collapse) => app.collapse(),
+                            Some(Action::Expand) => app.expand(),
+                            Some(Action::Tag) => app.toggle_tag(),
+                            Some(Action::TagMatching) => app.tag_matching(),
+                            Some(Action::InvertTags) => app.invert_tags(),
+                            Some(Action::UntagAll) => app.untag_all(),
+                            Some(Action::Signal) => app.signal_menu(),
+                            Some(Action::Priority) => app.priority_dialog(),
+                            Some(Action::Affinity) => app.affinity_editor(),
+                            Some(Action::Export) => app.export_prompt(),
+                            Some(Action::Slower) => app.slower(),
+                            Some(Action::Faster) => app.faster(),
+                            Some(Action::Inspect) => app.inspect(),
+                            Some(Action::Columns) => app.column_editor(),
+                            Some(Action::Palette) => app.cycle_palette(),
+                            Some(Action::SaveConfig) => app.save_config(),
+                            Some(Action::Help) => app.show_help(),
+                            None => {}
                         }
                     }
                 }
 
                 InputMode::Editing if key.kind == KeyEventKind::Press => match key.code {
                     KeyCode::Enter => app.submit_message(),
+                    KeyCode::Backspace => app.delete_char(),
+                    KeyCode::Esc => app.cancel_editing(),
                     KeyCode::Char(to_insert) => app.enter_char(to_insert),
                     _ => println!("Error. Enter letter or press return"),
                 },
                 InputMode::Editing => {}
+
+                _ if key.kind != KeyEventKind::Press => {}
+                InputMode::SignalMenu => match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => app.cancel_signal(),
+                    KeyCode::Char('j') | KeyCode::Down => app.signal_state.select_next(),
+                    KeyCode::Char('k') | KeyCode::Up => app.signal_state.select_previous(),
+                    KeyCode::Tab => app.signal_target = app.signal_target.next(),
+                    KeyCode::Enter => app.choose_signal(),
+                    _ => {}
+                },
+                InputMode::Priority => match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => app.input_mode = InputMode::Normal,
+                    KeyCode::Char('j') | KeyCode::Down => app.priority_state.select_next(),
+                    KeyCode::Char('k') | KeyCode::Up => app.priority_state.select_previous(),
+                    KeyCode::Char('l') | KeyCode::Right | KeyCode::Char('+') => {
+                        app.adjust_priority(1)
+                    }
+                    KeyCode::Char('h') | KeyCode::Left | KeyCode::Char('-') => {
+                        app.adjust_priority(-1)
+                    }
+                    KeyCode::PageUp => app.adjust_priority(10),
+                    KeyCode::PageDown => app.adjust_priority(-10),
+                    KeyCode::Enter => app.apply_priority(),
+                    _ => {}
+                },
+                InputMode::Affinity => {
+                    let cpus = app.affinity_cpus.len();
+                    let cursor = app.affinity_cursor;
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') => app.input_mode = InputMode::Normal,
+                        KeyCode::Char('l') | KeyCode::Right => {
+                            app.affinity_cursor = affinity::move_cursor(cursor, 1, cpus)
+                        }
+                        KeyCode::Char('h') | KeyCode::Left => {
+                            app.affinity_cursor = affinity::move_cursor(cursor, -1, cpus)
+                        }
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.affinity_cursor =
+                                affinity::move_cursor(cursor, affinity::LINE, cpus)
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.affinity_cursor =
+                                affinity::move_cursor(cursor, -affinity::LINE, cpus)
+                        }
+                        KeyCode::Char(' ') => app.toggle_cpu(),
+                        KeyCode::Char('a') => app.check_all_cpus(),
+                        KeyCode::Tab => app.affinity_all_threads = !app.affinity_all_threads,
+                        KeyCode::Enter => app.apply_affinity(),
+                        _ => {}
+                    }
+                }
+                InputMode::Threads => match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => app.input_mode = InputMode::Normal,
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        move_selection(&mut app.thread_state, app.thread_rows.len(), 1)
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        move_selection(&mut app.thread_state, app.thread_rows.len(), -1)
+                    }
+                    _ => {}
+                },
+                InputMode::Files => match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => app.input_mode = InputMode::Normal,
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        move_selection(&mut app.files_state, app.open_files.len(), 1)
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        move_selection(&mut app.files_state, app.open_files.len(), -1)
+                    }
+                    KeyCode::PageDown => {
+                        move_selection(&mut app.files_state, app.open_files.len(), 10)
+                    }
+                    KeyCode::PageUp => {
+                        move_selection(&mut app.files_state, app.open_files.len(), -10)
+                    }
+                    _ => {}
+                },
+                InputMode::Sockets => match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => app.input_mode = InputMode::Normal,
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        move_selection(&mut app.sockets_state, app.sockets.len(), 1)
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        move_selection(&mut app.sockets_state, app.sockets.len(), -1)
+                    }
+                    KeyCode::PageDown => {
+                        move_selection(&mut app.sockets_state, app.sockets.len(), 10)
+                    }
+                    KeyCode::PageUp => {
+                        move_selection(&mut app.sockets_state, app.sockets.len(), -10)
+                    }
+                    _ => {}
+                },
+                InputMode::MemoryMap => {
+                    let rows = smaps::row_count(&app);
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') => app.input_mode = InputMode::Normal,
+                        KeyCode::Char('m') => {
+                            app.merge_mappings = !app.merge_mappings;
+                            app.mappings_state.select(Some(0));
+                        }
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            move_selection(&mut app.mappings_state, rows, 1)
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            move_selection(&mut app.mappings_state, rows, -1)
+                        }
+                        KeyCode::PageDown => move_selection(&mut app.mappings_state, rows, 10),
+                        KeyCode::PageUp => move_selection(&mut app.mappings_state, rows, -10),
+                        _ => {}
+                    }
+                }
+                InputMode::FindPath => match key.code {
+                    KeyCode::Esc => app.input_mode = InputMode::Normal,
+                    KeyCode::Enter => app.find_holders(),
+                    KeyCode::Backspace => {
+                        app.fuser_input.pop();
+                    }
+                    KeyCode::Char(c) => app.fuser_input.push(c),
+                    _ => {}
+                },
+                InputMode::Report => match key.code {
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
+                        app.input_mode = InputMode::Normal
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        move_selection(&mut app.report_state, app.report.len(), 1)
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        move_selection(&mut app.report_state, app.report.len(), -1)
+                    }
+                    KeyCode::PageDown => {
+                        move_selection(&mut app.report_state, app.report.len(), 10)
+                    }
+                    KeyCode::PageUp => move_selection(&mut app.report_state, app.report.len(), -10),
+                    _ => {}
+                },
+                InputMode::Help => match key.code {
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') | KeyCode::Char('?') => {
+                        app.input_mode = InputMode::Normal
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        move_selection(&mut app.help_state, app.keymap.bindings().len(), 1)
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        move_selection(&mut app.help_state, app.keymap.bindings().len(), -1)
+                    }
+                    _ => {}
+                },
+                InputMode::Holders => match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => app.input_mode = InputMode::Normal,
+                    KeyCode::Enter => app.select_holder(),
+                    KeyCode::Char('r') => app.find_holders(),
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        move_selection(&mut app.holders_state, app.holders.len(), 1)
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        move_selection(&mut app.holders_state, app.holders.len(), -1)
+                    }
+                    KeyCode::PageDown => {
+                        move_selection(&mut app.holders_state, app.holders.len(), 10)
+                    }
+                    KeyCode::PageUp => {
+                        move_selection(&mut app.holders_state, app.holders.len(), -10)
+                    }
+                    _ => {}
+                },
+                InputMode::Details => match key.code {
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
+                        app.input_mode = InputMode::Normal
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => app.scroll_details(1),
+                    KeyCode::Char('k') | KeyCode::Up => app.scroll_details(-1),
+                    KeyCode::PageDown | KeyCode::Char(' ') => app.scroll_details(10),
+                    KeyCode::PageUp => app.scroll_details(-10),
+                    KeyCode::Home | KeyCode::Char('g') => app.detail_scroll = 0,
+                    KeyCode::End | KeyCode::Char('G') => app.scroll_details(isize::MAX),
+                    KeyCode::Char('y') => app.copy_details(true),
+                    KeyCode::Char('c') => app.copy_details(false),
+                    _ => {}
+                },
+                InputMode::Columns => match key.code {
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
+                        app.input_mode = InputMode::Normal
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => app.column_state.select_next(),
+                    KeyCode::Char('k') | KeyCode::Up => app.column_state.select_previous(),
+                    KeyCode::Char(' ') => app.toggle_column(),
+                    KeyCode::Char('J') => app.move_column(1),
+                    KeyCode::Char('K') => app.move_column(-1),
+                    KeyCode::Char('>') | KeyCode::Char('+') => app.resize_column(1),
+                    KeyCode::Char('<') | KeyCode::Char('-') => app.resize_column(-1),
+                    KeyCode::Char('a') => app.auto_width(),
+                    _ => {}
+                },
+                InputMode::SignalNumber => match key.code {
+                    KeyCode::Esc => app.cancel_signal(),
+                    KeyCode::Enter => app.submit_signal_number(),
+                    KeyCode::Backspace => {
+                        app.signal_input.pop();
+                    }
+                    KeyCode::Char(c) => app.signal_input.push(c),
+                    _ => {}
+                },
+                InputMode::Export => match key.code {
+                    KeyCode::Esc => app.input_mode = InputMode::Normal,
+                    KeyCode::Enter => app.export(),
+                    KeyCode::Backspace => {
+                        app.export_path.pop();
+                    }
+                    KeyCode::Char(c) => app.export_path.push(c),
+                    _ => {}
+                },
+                InputMode::ConfirmSignal => match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => app.confirm_signal(),
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.cancel_signal(),
+                    _ => {}
+                },
             }
         }
     }
@@ -292,10 +1633,38 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
 enum InputMode {
     Normal,
     Editing,
+    SignalMenu,
+    SignalNumber,
+    ConfirmSignal,
+    Export,
+    Columns,
+    Details,
+    Threads,
+    Files,
+    Sockets,
+    MemoryMap,
+    Priority,
+    Affinity,
+    FindPath,
+    Holders,
+    Report,
+    Help,
+}
+
+/// Move the selection of a popup table by `offset` rows, staying within its
+/// `len` rows.
+fn move_selection(state: &mut TableState, len: usize, offset: isize) {
+    let i = state
+        .selected()
+        .unwrap_or(0)
+        .saturating_add_signed(offset)
+        .min(len.saturating_sub(1));
+    state.select(Some(i));
 }
 
 fn ui(f: &mut Frame, app: &mut App) {
     let vertical = &Layout::vertical([
+        Constraint::Length(app.summary.height()),
         Constraint::Min(5),
         Constraint::Length(4),
         Constraint::Percentage(10),
@@ -304,81 +1673,192 @@ fn ui(f: &mut Frame, app: &mut App) {
 
     app.set_colors();
 
-    render_table(f, app, rects[0]);
+    summary::render_summary(f, app, rects[0]);
+
+    render_table(f, app, rects[1]);
+
+    render_scrollbar(f, app, rects[1]);
 
-    render_scrollbar(f, app, rects[0]);
+    render_footer(f, app, rects[2]);
 
-    render_footer(f, app, rects[1]);
+    render_filter(f, app, rects[3]);
 
-    render_filter(f, app, rects[2]);
+    match app.input_mode {
+        InputMode::SignalMenu => signal::render_signal_menu(f, app, rects[1]),
+        InputMode::SignalNumber => signal::render_signal_number(f, app, rects[1]),
+        InputMode::ConfirmSignal => signal::render_confirm(f, app, rects[1]),
+        InputMode::Export => export::render_export_prompt(f, app, rects[1]),
+        InputMode::Columns => column::render_column_editor(f, app, rects[1]),
+        InputMode::Details => detail::render_details(f, app, rects[1]),
+        InputMode::Threads => threads::render_threads(f, app, rects[1]),
+        InputMode::Files => files::render_files(f, app, rects[1]),
+        InputMode::Sockets => net::render_sockets(f, app, rects[1]),
+        InputMode::Affinity => affinity::render_affinity(f, app, rects[1]),
+        InputMode::Priority => priority::render_priority(f, app, rects[1]),
+        InputMode::MemoryMap => smaps::render_memory_map(f, app, rects[1]),
+        InputMode::FindPath => fuser::render_path_prompt(f, app, rects[1]),
+        InputMode::Holders => fuser::render_holders(f, app, rects[1]),
+        InputMode::Report => report::render_report(f, app, rects[1]),
+        InputMode::Help => help::render_help(f, app, rects[1]),
+        _ => {}
+    }
+}
+
+/// A rectangle of at most `width` x `height` centered in `area`, for popups.
+fn popup_area(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
 }
 
 fn render_table(f: &mut Frame, app: &mut App, area: Rect) {
     let header_style = Style::default()
+        .add_modifier(Modifier::BOLD)
         .fg(app.colors.header_fg)
         .bg(app.colors.header_bg);
     let selected_style = Style::default()
         .add_modifier(Modifier::REVERSED)
         .fg(app.colors.selected_style_fg);
 
-    let header = ["PID", "PATH", "File", "User"]
-        .into_iter()
-        .map(Cell::from)
+    let sort_indicator = if app.sort_descending { "▼" } else { "▲" };
+    let header = app
+        .columns
+        .iter()
+        .map(|&column| {
+            let label = column.label();
+            if column == app.sort_column {
+                Cell::from(format!("{label}{sort_indicator}"))
+            } else {
+                Cell::from(label)
+            }
+        })
         .collect::<Row>()
         .style(header_style)
         .height(1);
 
     // Note: TableState should be stored in your application state (not constructed in your render
     // method) so that the selected row is preserved across renders
-    let len = app.items[0].len();
-    app.scroll_state = app.scroll_state.position(0);
     let mut rows = vec![];
+    let mut visible_pids = vec![];
 
-    // transform array of array
-    if app.message.len() > 0 {
-        // filter by file by default, use user if set
-        let mut item_col: usize = 2;
-        if app.file_or_user == "user" {
-            item_col = 3
-        }
+    let shown = app.visible_rows();
+
+    // columns the user did not size fit their widest cell, the widest ones
+    // giving way when the terminal is too narrow for all of them
+    let wanted: Vec<u16> = app
+        .columns
+        .iter()
+        .map(|&column| {
+            app.widths[column.index()].unwrap_or_else(|| {
+                let content = shown
+                    .iter()
+                    .map(|&n| app.items[n][column.index()].width())
+                    .max()
+                    .unwrap_or(0);
+                // room for the sort indicator after the label
+                content.max(column.label().len() + 1).min(u16::MAX as usize) as u16
+            })
+        })
+        .collect();
+    let minimums: Vec<u16> = app
+        .columns
+        .iter()
+        .zip(&wanted)
+        .map(|(&column, &width)| match app.widths[column.index()] {
+            Some(_) => width,
+            None => column.min_width().min(width),
+        })
+        .collect();
+    // one space between columns, and one for the scrollbar
+    let available = area.width.saturating_sub(app.columns.len() as u16);
+    app.shown_widths = column::fit(&wanted, &minimums, available);
 
-        for n in 0..len {
-            if app.items[item_col][n].contains(&app.message[0]) {
-                rows.push(Row::new(vec![
-                    app.items[0][n].clone(),
-                    app.items[1][n].clone(),
-                    app.items[2][n].clone(),
-                    app.items[3][n].clone(),
-                ]));
+    for n in shown {
+        let bg = match rows.len() % 2 {
+            0 => app.colors.normal_row_color,
+            _ => app.colors.alt_row_color,
+        };
+        // new and exited rows are also bold and dim, so they stand out in the
+        // monochrome theme
+        let mut row_style = Style::new().bg(bg);
+        match app.rows[n].row_state {
+            RowState::Added => {
+                row_style = row_style
+                    .fg(app.colors.added_row_fg)
+                    .add_modifier(Modifier::BOLD)
             }
+            RowState::Removed => {
+                row_style = row_style
+                    .fg(app.colors.removed_row_fg)
+                    .add_modifier(Modifier::DIM)
+            }
+            RowState::Steady => {}
+        }
+        if app.rows[n].thread {
+            row_style = row_style.add_modifier(Modifier::ITALIC);
         }
-    } else {
-        for n in 0..len {
-            rows.push(Row::new(vec![
-                app.items[0][n].clone(),
-                app.items[1][n].clone(),
-                app.items[2][n].clone(),
-                app.items[3][n].clone(),
-            ]));
+        // underlined too, for the monochrome theme
+        if app.tagged.contains(&app.rows[n].pid) {
+            row_style = row_style
+                .fg(app.colors.selected_style_fg)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
         }
+
+        visible_pids.push(app.rows[n].pid);
+        rows.push(
+            Row::new(
+                app.columns
+                    .iter()
+                    .map(|column| app.items[n][column.index()].clone()),
+            )
+            .style(row_style),
+        );
     }
 
     app.filtered_rows = rows.len();
 
-    let widths = [
-        //  _ => app.colors.alt_row_color,
-        Constraint::Length(8),
-        Constraint::Length(52),
-        Constraint::Length(100),
-        Constraint::Length(30),
-    ];
+    // keep the selection on the same process if it is still listed, otherwise
+    // stay on the same row index
+    let selected = app
+        .selected_pid
+        .and_then(|pid| visible_pids.iter().position(|&p| p == pid))
+        .or(app.state.selected())
+        .map(|i| i.min(app.filtered_rows.saturating_sub(1)))
+        .unwrap_or(0);
+    app.state.select(Some(selected));
+    app.selected_pid = visible_pids.get(selected).copied();
+    app.visible_pids = visible_pids;
+    app.scroll_state = app
+        .scroll_state
+        .content_length(app.filtered_rows.saturating_sub(1) * ITEM_HEIGHT)
+        .position(selected * ITEM_HEIGHT);
+
+    let widths = app
+        .shown_widths
+        .iter()
+        .map(|&width| Constraint::Length(width));
 
+    let mut title = format!(
+        "Processes (refresh every {:.1}s)",
+        app.refresh_interval.as_secs_f32()
+    );
+    if app.inaccessible > 0 {
+        title.push_str(&format!(" - {} inaccessible", app.inaccessible));
+    }
+    if !app.tagged.is_empty() {
+        title.push_str(&format!(" - {} tagged", app.tagged.len()));
+    }
     let table = Table::new(rows, widths)
-        .block(Block::new().title("Processes"))
+        .block(Block::new().title(title))
         .column_spacing(1)
-        .style(Style::new().light_blue())
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
         .header(header)
-        .highlight_style(Style::new().reversed());
+        .highlight_style(selected_style);
 
     f.render_stateful_widget(table, area, &mut app.state);
 }
@@ -387,6 +1867,7 @@ fn render_scrollbar(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_stateful_widget(
         Scrollbar::default()
             .orientation(ScrollbarOrientation::VerticalRight)
+            .style(Style::new().fg(app.colors.footer_border_color))
             .begin_symbol(None)
             .end_symbol(None),
         area.inner(Margin {
@@ -398,24 +1879,49 @@ fn render_scrollbar(f: &mut Frame, app: &mut App, area: Rect) {
 }
 
 fn render_footer(f: &mut Frame, app: &App, area: Rect) {
-    let info_footer = Paragraph::new(Line::from(INFO_TEXT))
-        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
-        .centered()
-        .block(
-            Block::bordered()
-                .border_type(BorderType::Double)
-                .border_style(Style::new().fg(app.colors.footer_border_color)),
-        );
+    let info_footer = Paragraph::new(vec![
+        Line::from(app.keymap.footer()),
+        Line::from(app.status.as_str()).fg(app.colors.selected_style_fg),
+    ])
+    .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+    .centered()
+    .block(
+        Block::bordered()
+            .border_type(BorderType::Double)
+            .border_style(Style::new().fg(app.colors.footer_border_color)),
+    );
     f.render_widget(info_footer, area);
 }
 
 fn render_filter(f: &mut Frame, app: &App, area: Rect) {
-    let mut message = String::new();
-    if app.message.len() > 0 {
-        message = app.message[0].clone();
+    let column = match app.file_or_user.as_str() {
+        "user" => "user",
+        "all" => "all columns",
+        "port" => "listening port",
+        _ => "file",
+    };
+    let mut lines: Vec<Line> = vec![];
+    if let InputMode::Editing = app.input_mode {
+        lines.push(
+            vec![
+                format!("Filter {column}: ").fg(app.colors.footer_border_color),
+                format!("{}█", app.input).fg(app.colors.selected_style_fg),
+            ]
+            .into(),
+        );
+    } else if !app.filters.is_empty() {
+        lines.push(
+            vec![
+                "Filter is: ".fg(app.colors.footer_border_color),
+                filter::describe(&app.filters).fg(app.colors.selected_style_fg),
+            ]
+            .into(),
+        );
+    }
+    if let Some(err) = &app.filter_error {
+        lines.push(Line::from(err.as_str()).fg(app.colors.removed_row_fg));
     }
-    let line: Line = vec!["Filter is: ".light_blue(), message.cyan()].into();
-    let filter_info = List::new(line)
+    let filter_info = List::new(lines)
         .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
         .block(
             Block::bordered()
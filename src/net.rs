@@ -0,0 +1,445 @@
+//! Network sockets of a process, matched by inode between the descriptors in
+//! `/proc/<pid>/fd` and the kernel's tables in `/proc/net`, like `ss -p`
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    terminal::Frame,
+    widgets::{Block, BorderType, Clear, Row, Table},
+};
+
+use crate::{popup_area, App};
+
+/// The internet socket tables, with whether their addresses are IPv6
+const INET_TABLES: [(&str, bool); 4] = [
+    ("tcp", false),
+    ("tcp6", true),
+    ("udp", false),
+    ("udp6", true),
+];
+
+/// `__SO_ACCEPTCON` in the flags of `/proc/net/unix`, set on listening sockets
+const UNIX_LISTENING: u32 = 0x10000;
+
+/// One socket, with the names `ss` uses for protocols and states
+pub struct Socket {
+    /// `tcp`, `tcp6`, `udp`, `udp6`, `u_str`, `u_dgr`, `u_seq`, or `other`
+    /// for sockets in no table read, such as netlink ones
+    pub protocol: String,
+    pub state: String,
+    pub local: String,
+    pub remote: String,
+    /// Bytes waiting to be read, or connections to be accepted when listening
+    pub receive_queue: Option<u64>,
+    /// Bytes not yet acknowledged by the peer
+    pub send_queue: Option<u64>,
+    pub inode: u64,
+    /// Port of a listening TCP socket or of an unconnected UDP one
+    pub listening_port: Option<u16>,
+}
+
+/// The sockets of `pid`, read from the tables of its own network namespace.
+pub fn list(pid: u32) -> io::Result<Vec<Socket>> {
+    let inodes = socket_inodes(pid)?;
+    let mut table = read_tables(&format!("/proc/{pid}/net"));
+
+    let mut sockets: Vec<Socket> = inodes
+        .into_iter()
+        .map(|inode| {
+            table.remove(&inode).unwrap_or_else(|| Socket {
+                protocol: String::from("other"),
+                state: String::new(),
+                local: format!("inode {inode}"),
+                remote: String::new(),
+                receive_queue: None,
+                send_queue: None,
+                inode,
+                listening_port: None,
+            })
+        })
+        .collect();
+    sockets.sort_by(|a, b| {
+        a.protocol
+            .cmp(&b.protocol)
+            .then_with(|| a.local.cmp(&b.local))
+    });
+    Ok(sockets)
+}
+
+/// The ports each process listens on, by PID. Only sockets of the network
+/// namespace rustytop runs in are seen.
+pub fn listeners() -> HashMap<u32, Vec<u16>> {
+    let ports: HashMap<u64, u16> = read_tables("/proc/net")
+        .into_iter()
+        .filter_map(|(inode, socket)| Some((inode, socket.listening_port?)))
+        .collect();
+    let mut listeners: HashMap<u32, Vec<u16>> = HashMap::new();
+    if ports.is_empty() {
+        return listeners;
+    }
+
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return listeners;
+    };
+    for pid in entries.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok()) {
+        // descriptors of other users' processes are not readable without privileges
+        let Ok(inodes) = socket_inodes(pid) else {
+            continue;
+        };
+        for inode in inodes {
+            if let Some(&port) = ports.get(&inode) {
+                let process_ports = listeners.entry(pid).or_default();
+                if !process_ports.contains(&port) {
+                    process_ports.push(port);
+                }
+            }
+        }
+    }
+    listeners
+}
+
+/// Inodes of the `socket:[inode]` descriptors of `pid`.
+fn socket_inodes(pid: u32) -> io::Result<Vec<u64>> {
+    let mut inodes = vec![];
+    for entry in fs::read_dir(format!("/proc/{pid}/fd"))? {
+        let Ok(target) = fs::read_link(entry?.path()) else {
+            continue;
+        };
+        if let Some(inode) = target
+            .to_str()
+            .and_then(|target| target.strip_prefix("socket:["))
+            .and_then(|target| target.strip_suffix(']'))
+            .and_then(|inode| inode.parse().ok())
+        {
+            inodes.push(inode);
+        }
+    }
+    Ok(inodes)
+}
+
+/// Every socket in the tables under `dir`, by inode. Tables that cannot be
+/// read, such as `tcp6` with IPv6 disabled, are skipped.
+fn read_tables(dir: &str) -> HashMap<u64, Socket> {
+    let mut sockets = HashMap::new();
+    for (protocol, v6) in INET_TABLES {
+        let Ok(table) = fs::read_to_string(format!("{dir}/{protocol}")) else {
+            continue;
+        };
+        for socket in table
+            .lines()
+            .skip(1)
+            .filter_map(|line| parse_inet(line, protocol, v6))
+        {
+            sockets.insert(socket.inode, socket);
+        }
+    }
+    if let Ok(table) = fs::read_to_string(format!("{dir}/unix")) {
+        for socket in table.lines().skip(1).filter_map(parse_unix) {
+            sockets.insert(socket.inode, socket);
+        }
+    }
+    sockets
+}
+
+/// Parse a line of `/proc/net/tcp` and the like:
+/// `sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode ...`
+fn parse_inet(line: &str, protocol: &str, v6: bool) -> Option<Socket> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let local = address(fields.get(1)?, v6)?;
+    let remote = address(fields.get(2)?, v6)?;
+    let state = u8::from_str_radix(fields.get(3)?, 16).ok()?;
+    let (send_queue, receive_queue) = fields.get(4)?.split_once(':')?;
+    let inode = fields.get(9)?.parse().ok()?;
+
+    let tcp = protocol.starts_with("tcp");
+    // TCP_LISTEN, and TCP_CLOSE which UDP uses for sockets not connected
+    let listening = (tcp && state == 0x0a) || (!tcp && state == 0x07);
+    Some(Socket {
+        protocol: protocol.to_string(),
+        state: tcp_state(state).to_string(),
+        local: local.to_string(),
+        remote: match remote.ip().is_unspecified() && remote.port() == 0 {
+            true => String::from("*"),
+            false => remote.to_string(),
+        },
+        receive_queue: u64::from_str_radix(receive_queue, 16).ok(),
+        send_queue: u64::from_str_radix(send_queue, 16).ok(),
+        inode,
+        listening_port: Some(local.port()).filter(|&port| listening && port != 0),
+    })
+}
+
+/// Parse an `ADDRESS:PORT` of the inet tables, in hexadecimal. The address is
+/// printed as 32 bit words in host byte order, which makes the bytes in
+/// memory those of the address in network order.
+fn address(text: &str, v6: bool) -> Option<SocketAddr> {
+    let (address, port) = text.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = vec![];
+    for word in 0..address.len() / 8 {
+        let word = u32::from_str_radix(address.get(word * 8..word * 8 + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match v6 {
+        true => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        false => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// The name `ss` gives a `TCP_*` state of `include/net/tcp_states.h`.
+fn tcp_state(state: u8) -> &'static str {
+    match state {
+        0x01 => "ESTAB",
+        0x02 => "SYN-SENT",
+        0x03 => "SYN-RECV",
+        0x04 => "FIN-WAIT-1",
+        0x05 => "FIN-WAIT-2",
+        0x06 => "TIME-WAIT",
+        0x07 => "UNCONN",
+        0x08 => "CLOSE-WAIT",
+        0x09 => "LAST-ACK",
+        0x0a => "LISTEN",
+        0x0b => "CLOSING",
+        0x0c => "NEW-SYN-RECV",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Parse a line of `/proc/net/unix`:
+/// `Num RefCount Protocol Flags Type St Inode [Path]`, the path being absent
+/// for unnamed sockets and starting with `@` for abstract ones.
+fn parse_unix(line: &str) -> Option<Socket> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let flags = u32::from_str_radix(fields.get(3)?, 16).ok()?;
+    let kind = u32::from_str_radix(fields.get(4)?, 16).ok()?;
+    let state = u32::from_str_radix(fields.get(5)?, 16).ok()?;
+    let inode = fields.get(6)?.parse().ok()?;
+
+    // the path may contain spaces, it is everything after the inode
+    let mut path = line;
+    for _ in 0..7 {
+        path = path.trim_start();
+        path = path.split_once(' ').map_or("", |(_, rest)| rest);
+    }
+    let path = path.trim();
+
+    Some(Socket {
+        protocol: match kind {
+            1 => "u_str",
+            2 => "u_dgr",
+            5 => "u_seq",
+            _ => "unix",
+        }
+        .to_string(),
+        // SS_UNCONNECTED, SS_CONNECTING, SS_CONNECTED and SS_DISCONNECTING
+        state: match (flags & UNIX_LISTENING != 0, state) {
+            (true, _) => "LISTEN",
+            (false, 1) => "UNCONN",
+            (false, 2) => "SYN-SENT",
+            (false, 3) => "ESTAB",
+            (false, 4) => "CLOSING",
+            _ => "UNKNOWN",
+        }
+        .to_string(),
+        local: match path.is_empty() {
+            true => String::from("*"),
+            false => path.to_string(),
+        },
+        remote: String::from("*"),
+        receive_queue: None,
+        send_queue: None,
+        inode,
+        listening_port: None,
+    })
+}
+
+pub fn render_sockets(f: &mut Frame, app: &mut App, area: Rect) {
+    let header = ["PROTO", "STATE", "RECV-Q", "SEND-Q", "LOCAL", "REMOTE"]
+        .into_iter()
+        .collect::<Row>()
+        .style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .fg(app.colors.header_fg)
+                .bg(app.colors.header_bg),
+        );
+    let queue = |bytes: Option<u64>| bytes.map(|bytes| bytes.to_string()).unwrap_or_default();
+    let rows = app.sockets.iter().map(|socket| {
+        Row::new([
+            socket.protocol.clone(),
+            socket.state.clone(),
+            queue(socket.receive_queue),
+            queue(socket.send_queue),
+            socket.local.clone(),
+            socket.remote.clone(),
+        ])
+    });
+    let widths = [
+        Constraint::Length(6),
+        Constraint::Length(12),
+        Constraint::Length(7),
+        Constraint::Length(7),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+    ];
+
+    let listening = app
+        .sockets
+        .iter()
+        .filter(|socket| socket.state == "LISTEN" || socket.listening_port.is_some())
+        .count();
+    let area = popup_area(area, area.width.saturating_sub(4), area.height);
+    let table = Table::new(rows, widths)
+        .header(header)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title(format!(
+                    "Sockets of process {}: {}, {listening} listening",
+                    app.sockets_pid,
+                    app.sockets.len()
+                ))
+                .title_bottom("(↑/↓) move | (Esc) close"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut app.sockets_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the inet tables print addresses in host byte order, these lines come
+    // from a little endian machine
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn parses_inet_lines() {
+        let listening = parse_inet(
+            "   0: 0100007F:BC8F 00000000:0000 0A 00000000:00000002 00:00000000 00000000 \
+             65534        0 957 1 00000000681f7c58 100 0 0 10 0",
+            "tcp",
+            false,
+        )
+        .unwrap();
+        assert_eq!(listening.protocol, "tcp");
+        assert_eq!(listening.state, "LISTEN");
+        assert_eq!(listening.local, "127.0.0.1:48271");
+        assert_eq!(listening.remote, "*");
+        assert_eq!(listening.receive_queue, Some(2));
+        assert_eq!(listening.send_queue, Some(0));
+        assert_eq!(listening.inode, 957);
+        assert_eq!(listening.listening_port, Some(48271));
+
+        let connected = parse_inet(
+            "   3: 0F02000A:0016 0202000A:D431 01 00000024:00000000 01:00000019 00000000 \
+             0        0 40721 4 0000000000000000 20 4 31 10 -1",
+            "tcp",
+            false,
+        )
+        .unwrap();
+        assert_eq!(connected.state, "ESTAB");
+        assert_eq!(connected.local, "10.0.2.15:22");
+        assert_eq!(connected.remote, "10.0.2.2:54321");
+        assert_eq!(connected.send_queue, Some(0x24));
+        assert_eq!(connected.listening_port, None);
+
+        let unconnected = parse_inet(
+            "  12: 00000000:0044 00000000:0000 07 00000000:00000000 00:00000000 00000000 \
+             0        0 1234 2 0000000000000000 0",
+            "udp",
+            false,
+        )
+        .unwrap();
+        assert_eq!(unconnected.state, "UNCONN");
+        assert_eq!(unconnected.local, "0.0.0.0:68");
+        assert_eq!(unconnected.listening_port, Some(68));
+
+        assert!(parse_inet("  sl  local_address rem_address   st", "tcp", false).is_none());
+        assert!(parse_inet("   0: 0100007F:BC8F", "tcp", false).is_none());
+    }
+
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(
+            address("0100007F:0050", false),
+            Some("127.0.0.1:80".parse().unwrap())
+        );
+        assert_eq!(
+            address("00000000000000000000000001000000:1F90", true),
+            Some("[::1]:8080".parse().unwrap())
+        );
+        assert_eq!(
+            address("B80D0120000000000000000001000000:0016", true),
+            Some("[2001:db8::1]:22".parse().unwrap())
+        );
+        // a v4 address in a v6 table, or the other way round
+        assert_eq!(address("0100007F:0050", true), None);
+        assert_eq!(
+            address("00000000000000000000000001000000:0050", false),
+            None
+        );
+        assert_eq!(address("0100007F", false), None);
+        assert_eq!(address("0100007G:0050", false), None);
+        assert_eq!(address("0100007F:10000", false), None);
+    }
+
+    #[test]
+    fn names_tcp_states_like_ss() {
+        assert_eq!(tcp_state(0x01), "ESTAB");
+        assert_eq!(tcp_state(0x06), "TIME-WAIT");
+        assert_eq!(tcp_state(0x0a), "LISTEN");
+        assert_eq!(tcp_state(0x0c), "NEW-SYN-RECV");
+        assert_eq!(tcp_state(0x00), "UNKNOWN");
+        assert_eq!(tcp_state(0xff), "UNKNOWN");
+    }
+
+    #[test]
+    fn parses_unix_lines() {
+        let listening = parse_unix(
+            "00000000028d7a06: 00000002 00000000 00010000 0001 01 49037 /run/my app/sock",
+        )
+        .unwrap();
+        assert_eq!(listening.protocol, "u_str");
+        assert_eq!(listening.state, "LISTEN");
+        assert_eq!(listening.local, "/run/my app/sock");
+        assert_eq!(listening.inode, 49037);
+        assert_eq!(listening.listening_port, None);
+
+        let unnamed =
+            parse_unix("000000000cbf0a8b: 00000003 00000000 00000000 0001 03   658").unwrap();
+        assert_eq!(unnamed.state, "ESTAB");
+        assert_eq!(unnamed.local, "*");
+        assert_eq!(unnamed.inode, 658);
+
+        let abstract_socket = parse_unix(
+            "0000000000000000: 00000002 00000000 00000000 0002 01 17330 @/tmp/.X11-unix/X0",
+        )
+        .unwrap();
+        assert_eq!(abstract_socket.protocol, "u_dgr");
+        assert_eq!(abstract_socket.state, "UNCONN");
+        assert_eq!(abstract_socket.local, "@/tmp/.X11-unix/X0");
+
+        let seqpacket =
+            parse_unix("0000000000000000: 00000002 00000000 00000000 0005 04 99").unwrap();
+        assert_eq!(seqpacket.protocol, "u_seq");
+        assert_eq!(seqpacket.state, "CLOSING");
+
+        assert!(parse_unix("Num       RefCount Protocol Flags    Type St Inode Path").is_none());
+    }
+}
@@ -0,0 +1,600 @@
+//! Nice value, scheduling policy and I/O priority of processes: reading them
+//! for the table and changing them from the priority dialog
+
+use std::{fmt, fs, io};
+
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style, Stylize},
+    terminal::Frame,
+    text::Line,
+    widgets::{Block, BorderType, Clear, List},
+};
+use serde::Serialize;
+
+use crate::{popup_area, threads, App};
+
+/// `IOPRIO_WHO_PROCESS` of `linux/ioprio.h`, which despite its name targets
+/// a single thread
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+/// The class is in the bits above the level
+const IOPRIO_CLASS_SHIFT: u32 = 13;
+
+/// Scheduling policies the dialog offers. Deadline scheduling needs
+/// parameters of its own and is only shown.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    #[default]
+    Other,
+    Batch,
+    Idle,
+    Fifo,
+    Rr,
+    Deadline,
+}
+
+impl Policy {
+    /// The policies the dialog cycles through
+    const CHOICES: [Policy; 5] = [
+        Policy::Other,
+        Policy::Batch,
+        Policy::Idle,
+        Policy::Fifo,
+        Policy::Rr,
+    ];
+
+    /// The policy of a `SCHED_*` value, as in field 41 of `/proc/<pid>/stat`.
+    pub fn from_raw(raw: i64) -> Option<Self> {
+        let policy = match raw as libc::c_int {
+            libc::SCHED_OTHER => Policy::Other,
+            libc::SCHED_BATCH => Policy::Batch,
+            libc::SCHED_IDLE => Policy::Idle,
+            libc::SCHED_FIFO => Policy::Fifo,
+            libc::SCHED_RR => Policy::Rr,
+            6 => Policy::Deadline,
+            _ => return None,
+        };
+        Some(policy)
+    }
+
+    fn raw(self) -> libc::c_int {
+        match self {
+            Policy::Other => libc::SCHED_OTHER,
+            Policy::Batch => libc::SCHED_BATCH,
+            Policy::Idle => libc::SCHED_IDLE,
+            Policy::Fifo => libc::SCHED_FIFO,
+            Policy::Rr => libc::SCHED_RR,
+            Policy::Deadline => 6,
+        }
+    }
+
+    /// Whether the policy is a real-time one, the only ones with a priority
+    pub fn realtime(self) -> bool {
+        matches!(self, Policy::Fifo | Policy::Rr)
+    }
+
+    /// Short name as in the `CLS` column of `ps`.
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Policy::Other => "TS",
+            Policy::Batch => "B",
+            Policy::Idle => "IDL",
+            Policy::Fifo => "FF",
+            Policy::Rr => "RR",
+            Policy::Deadline => "DLN",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Policy::Other => "SCHED_OTHER",
+            Policy::Batch => "SCHED_BATCH",
+            Policy::Idle => "SCHED_IDLE",
+            Policy::Fifo => "SCHED_FIFO",
+            Policy::Rr => "SCHED_RR",
+            Policy::Deadline => "SCHED_DEADLINE",
+        }
+    }
+}
+
+/// I/O scheduling classes, as `ionice` names them
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoClass {
+    /// No class set, the I/O scheduler derives one from the nice value
+    #[default]
+    None,
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+impl IoClass {
+    const ALL: [IoClass; 4] = [
+        IoClass::None,
+        IoClass::Realtime,
+        IoClass::BestEffort,
+        IoClass::Idle,
+    ];
+
+    /// Whether the class has levels, from 0 (highest) to 7
+    fn has_levels(self) -> bool {
+        matches!(self, IoClass::Realtime | IoClass::BestEffort)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            IoClass::None => "none",
+            IoClass::Realtime => "realtime",
+            IoClass::BestEffort => "best-effort",
+            IoClass::Idle => "idle",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IoPriority {
+    pub class: IoClass,
+    pub level: u8,
+}
+
+impl IoPriority {
+    fn from_raw(raw: libc::c_int) -> Self {
+        let class = IoClass::ALL
+            .get((raw >> IOPRIO_CLASS_SHIFT) as usize)
+            .copied()
+            .unwrap_or(IoClass::None);
+        Self {
+            class,
+            level: (raw & ((1 << IOPRIO_CLASS_SHIFT) - 1)) as u8,
+        }
+    }
+
+    fn raw(self) -> libc::c_int {
+        let class = IoClass::ALL
+            .iter()
+            .position(|&class| class == self.class)
+            .unwrap_or(0) as libc::c_int;
+        let level = if self.class.has_levels() {
+            self.level
+        } else {
+            0
+        };
+        class << IOPRIO_CLASS_SHIFT | libc::c_int::from(level)
+    }
+}
+
+impl fmt::Display for IoPriority {
+    /// `be/4`, `rt/0`, `idle` or `none`, like `iotop`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.class {
+            IoClass::None => write!(f, "none"),
+            IoClass::Realtime => write!(f, "rt/{}", self.level),
+            IoClass::BestEffort => write!(f, "be/{}", self.level),
+            IoClass::Idle => write!(f, "idle"),
+        }
+    }
+}
+
+/// Exported as displayed, which keeps CSV rows flat
+impl Serialize for IoPriority {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The I/O priority of thread `tid`, if the kernel lets us read it.
+pub fn io_priority(tid: u32) -> Option<IoPriority> {
+    // SAFETY: ioprio_get only reads its integer arguments
+    let raw = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, tid) };
+    (raw >= 0).then(|| IoPriority::from_raw(raw as libc::c_int))
+}
+
+/// Everything the priority dialog edits
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Settings {
+    pub nice: i32,
+    pub policy: Policy,
+    /// Priority of the real-time policies, 1 to 99
+    pub rt_priority: u32,
+    pub io: IoPriority,
+}
+
+/// The fields of the dialog, in display order
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Nice,
+    Policy,
+    RtPriority,
+    IoClass,
+    IoLevel,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 5] = [
+        Setting::Nice,
+        Setting::Policy,
+        Setting::RtPriority,
+        Setting::IoClass,
+        Setting::IoLevel,
+    ];
+}
+
+impl Settings {
+    /// The current settings of task `tid`, `None` if it has exited.
+    pub fn read(tid: u32) -> Option<Self> {
+        let stat = fs::read_to_string(format!("/proc/{tid}/stat")).ok()?;
+        Self::parse(&stat, io_priority(tid).unwrap_or_default())
+    }
+
+    /// The settings in the `/proc/<tid>/stat` line `stat`, with the I/O
+    /// priority, which is not in there, given as `io`.
+    fn parse(stat: &str, io: IoPriority) -> Option<Self> {
+        let (_, fields) = stat.rsplit_once(')')?;
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        // fields 19, 40 and 41, counting from the state as 3
+        let field = |n: usize| {
+            fields
+                .get(n - 3)
+                .and_then(|value| value.parse::<i64>().ok())
+        };
+        Some(Self {
+            nice: field(19)? as i32,
+            policy: field(41)
+                .and_then(Policy::from_raw)
+                .unwrap_or(Policy::Other),
+            rt_priority: field(40).unwrap_or(0) as u32,
+            io,
+        })
+    }
+
+    /// Step `setting` by `step` within its range, cycling through choices.
+    pub fn adjust(&mut self, setting: Setting, step: i32) {
+        match setting {
+            Setting::Nice => self.nice = (self.nice + step).clamp(-20, 19),
+            Setting::Policy => {
+                let i = Policy::CHOICES
+                    .iter()
+                    .position(|&policy| policy == self.policy)
+                    .unwrap_or(0) as i32;
+                let count = Policy::CHOICES.len() as i32;
+                self.policy = Policy::CHOICES[(i + step.signum()).rem_euclid(count) as usize];
+                if self.policy.realtime() && self.rt_priority == 0 {
+                    self.rt_priority = 1;
+                }
+            }
+            Setting::RtPriority if self.policy.realtime() => {
+                self.rt_priority = (self.rt_priority as i32 + step).clamp(1, 99) as u32
+            }
+            Setting::IoClass => {
+                let i = IoClass::ALL
+                    .iter()
+                    .position(|&class| class == self.io.class)
+                    .unwrap_or(0) as i32;
+                let count = IoClass::ALL.len() as i32;
+                self.io.class = IoClass::ALL[(i + step.signum()).rem_euclid(count) as usize];
+            }
+            Setting::IoLevel if self.io.class.has_levels() => {
+                self.io.level = (i32::from(self.io.level) + step).clamp(0, 7) as u8
+            }
+            Setting::RtPriority | Setting::IoLevel => {}
+        }
+    }
+}
+
+/// The error of the last system call, explained with `hint` when the kernel
+/// refused for lack of privilege.
+pub fn refusal(hint: &str) -> io::Error {
+    let err = io::Error::last_os_error();
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => io::Error::new(err.kind(), format!("{err}: {hint}")),
+        _ => err,
+    }
+}
+
+/// Apply the settings that differ between `old` and `new` to one task.
+fn apply_task(tid: u32, old: &Settings, new: &Settings) -> io::Result<()> {
+    if new.policy != old.policy || new.rt_priority != old.rt_priority {
+        let param = libc::sched_param {
+            sched_priority: match new.policy.realtime() {
+                true => new.rt_priority as libc::c_int,
+                false => 0,
+            },
+        };
+        // SAFETY: param is a valid sched_param for the duration of the call
+        if unsafe { libc::sched_setscheduler(tid as libc::pid_t, new.policy.raw(), &param) } != 0 {
+            return Err(refusal(
+                "real-time policies and processes of other users need root or CAP_SYS_NICE",
+            ));
+        }
+    }
+    if new.nice != old.nice {
+        // SAFETY: setpriority has no memory safety requirements
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, new.nice) } != 0 {
+            // EACCES for lowering it, EPERM for a process of another user
+            return Err(match io::Error::last_os_error().raw_os_error() {
+                Some(libc::EACCES) => refusal("lowering the nice value needs root or CAP_SYS_NICE"),
+                _ => refusal("processes of other users need root or CAP_SYS_NICE"),
+            });
+        }
+    }
+    if new.io != old.io {
+        // SAFETY: ioprio_set only reads its integer arguments
+        let result =
+            unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, new.io.raw()) };
+        if result != 0 {
+            return Err(refusal(
+                "the realtime class needs root or CAP_SYS_ADMIN, processes of other users CAP_SYS_NICE",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Apply the settings changed from `old` to `new` to every thread of each
+/// target, given as a PID and whether it is a thread, giving the outcome by
+/// PID.
+pub fn apply(
+    targets: &[(u32, bool)],
+    old: &Settings,
+    new: &Settings,
+) -> Vec<(u32, io::Result<()>)> {
+    targets
+        .iter()
+        .map(|&(pid, thread)| {
+            // nice values and policies belong to threads, changing those of a
+            // process means changing those of each of its threads
+            let tids = match thread {
+                true => vec![pid],
+                false => threads::tids(pid),
+            };
+            for tid in tids {
+                match apply_task(tid, old, new) {
+                    Ok(()) => {}
+                    // threads may exit while we go through them
+                    Err(err) if err.raw_os_error() == Some(libc::ESRCH) && tid != pid => {}
+                    Err(err) => return (pid, Err(err)),
+                }
+            }
+            (pid, Ok(()))
+        })
+        .collect()
+}
+
+/// Describe what `apply` did in a sentence for the status line.
+pub fn summary(results: &[(u32, io::Result<()>)]) -> String {
+    let failures: Vec<(u32, &io::Error)> = results
+        .iter()
+        .filter_map(|(pid, result)| Some((*pid, result.as_ref().err()?)))
+        .collect();
+    match (results, failures.as_slice()) {
+        ([(pid, _)], []) => format!("Changed the priority of {pid}"),
+        (_, []) => format!("Changed the priority of {} processes", results.len()),
+        ([_], [(pid, err)]) => format!("Cannot change the priority of {pid}: {err}"),
+        (_, [(pid, err), ..]) => format!(
+            "Changed the priority of {} of {} processes, {pid}: {err}",
+            results.len() - failures.len(),
+            results.len()
+        ),
+    }
+}
+
+pub fn render_priority(f: &mut Frame, app: &mut App, area: Rect) {
+    let (old, new) = (app.priority_old, app.priority_new);
+    let value = |setting: Setting| match setting {
+        Setting::Nice => new.nice.to_string(),
+        Setting::Policy => new.policy.name().to_string(),
+        Setting::RtPriority if new.policy.realtime() => new.rt_priority.to_string(),
+        Setting::RtPriority => String::from("n/a, FIFO and RR only"),
+        Setting::IoClass => new.io.class.name().to_string(),
+        Setting::IoLevel if new.io.class.has_levels() => new.io.level.to_string(),
+        Setting::IoLevel => String::from("n/a, realtime and best-effort only"),
+    };
+    let changed = |setting: Setting| match setting {
+        Setting::Nice => new.nice != old.nice,
+        Setting::Policy => new.policy != old.policy,
+        Setting::RtPriority => new.rt_priority != old.rt_priority,
+        Setting::IoClass => new.io.class != old.io.class,
+        Setting::IoLevel => new.io.level != old.io.level,
+    };
+    let items: Vec<Line> = Setting::ALL
+        .iter()
+        .map(|&setting| {
+            let label = match setting {
+                Setting::Nice => "Nice (-20 to 19)",
+                Setting::Policy => "Policy",
+                Setting::RtPriority => "RT priority (1 to 99)",
+                Setting::IoClass => "I/O class",
+                Setting::IoLevel => "I/O level (0 to 7)",
+            };
+            let value = value(setting);
+            let line = match value.starts_with("n/a") {
+                true => Line::from(format!("{label:<22}   {value}")),
+                false => Line::from(format!("{label:<22} ◀ {value} ▶")),
+            };
+            match changed(setting) {
+                true => line.add_modifier(Modifier::BOLD),
+                false => line,
+            }
+        })
+        .collect();
+
+    let title = match app.priority_targets.as_slice() {
+        [(pid, _)] => format!("Priority of process {pid}"),
+        targets => format!("Priority of {} processes", targets.len()),
+    };
+    let area = popup_area(area, 64, items.len() as u16 + 2);
+    let dialog = List::new(items)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title(title)
+                .title_bottom("(↑/↓) field | (←/→) change | (Enter) apply | (Esc) cancel"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(dialog, area, &mut app.priority_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `/proc/<tid>/stat` line with the given nice value, real-time
+    /// priority and policy, of a task whose name has parentheses in it.
+    fn stat(nice: i32, rt_priority: u32, policy: i32) -> String {
+        format!(
+            "1234 (irq/9 (acpi)) S 2 0 0 0 -1 2129984 0 0 0 0 0 0 0 0 20 {nice} 1 0 12 0 0 \
+             18446744073709551615 0 0 0 0 0 0 0 2147483647 0 0 0 0 17 0 {rt_priority} {policy} \
+             0 0 0 0 0 0 0 0 0 0 0"
+        )
+    }
+
+    #[test]
+    fn io_priority_round_trips() {
+        let cases = [
+            (0, IoClass::None, 0),
+            (1 << 13, IoClass::Realtime, 0),
+            (2 << 13 | 4, IoClass::BestEffort, 4),
+            (2 << 13 | 7, IoClass::BestEffort, 7),
+            (3 << 13, IoClass::Idle, 0),
+        ];
+        for (raw, class, level) in cases {
+            let priority = IoPriority::from_raw(raw);
+            assert!(priority == IoPriority { class, level }, "{raw}");
+            assert_eq!(priority.raw(), raw);
+        }
+        assert_eq!(IoPriority::from_raw(2 << 13 | 4).to_string(), "be/4");
+        assert_eq!(IoPriority::from_raw(1 << 13 | 2).to_string(), "rt/2");
+        // unknown classes read as none, levels of classes without any are
+        // dropped when written
+        assert!(IoPriority::from_raw(5 << 13).class == IoClass::None);
+        let idle = IoPriority {
+            class: IoClass::Idle,
+            level: 3,
+        };
+        assert_eq!(idle.raw(), 3 << 13);
+    }
+
+    #[test]
+    fn policies_from_raw() {
+        for policy in Policy::CHOICES.into_iter().chain([Policy::Deadline]) {
+            assert!(Policy::from_raw(i64::from(policy.raw())) == Some(policy));
+        }
+        assert!(Policy::from_raw(0) == Some(Policy::Other));
+        assert!(Policy::from_raw(1) == Some(Policy::Fifo));
+        assert!(Policy::from_raw(2) == Some(Policy::Rr));
+        assert!(Policy::from_raw(3) == Some(Policy::Batch));
+        assert!(Policy::from_raw(5) == Some(Policy::Idle));
+        assert!(Policy::from_raw(6) == Some(Policy::Deadline));
+        assert!(Policy::from_raw(4).is_none());
+        assert!(Policy::from_raw(-1).is_none());
+    }
+
+    #[test]
+    fn parses_stat_fields() {
+        let io = IoPriority::from_raw(2 << 13 | 4);
+        let settings = Settings::parse(&stat(-5, 0, 3), io).unwrap();
+        assert_eq!(settings.nice, -5);
+        assert!(settings.policy == Policy::Batch);
+        assert_eq!(settings.rt_priority, 0);
+        assert!(settings.io == io);
+
+        let settings = Settings::parse(&stat(0, 50, 1), IoPriority::default()).unwrap();
+        assert!(settings.policy == Policy::Fifo);
+        assert_eq!(settings.rt_priority, 50);
+
+        // unknown policies read as the default one
+        let settings = Settings::parse(&stat(19, 0, 4), IoPriority::default()).unwrap();
+        assert_eq!(settings.nice, 19);
+        assert!(settings.policy == Policy::Other);
+
+        assert!(Settings::parse("1234 (cut short) S 2 0", IoPriority::default()).is_none());
+        assert!(Settings::parse("", IoPriority::default()).is_none());
+    }
+
+    #[test]
+    fn adjust_clamps_ranges() {
+        let mut settings = Settings::default();
+        settings.adjust(Setting::Nice, -15);
+        assert_eq!(settings.nice, -15);
+        settings.adjust(Setting::Nice, -10);
+        assert_eq!(settings.nice, -20);
+        settings.adjust(Setting::Nice, 50);
+        assert_eq!(settings.nice, 19);
+
+        // only the real-time policies have a priority
+        settings.adjust(Setting::RtPriority, 5);
+        assert_eq!(settings.rt_priority, 0);
+        settings.policy = Policy::Fifo;
+        settings.rt_priority = 1;
+        settings.adjust(Setting::RtPriority, -10);
+        assert_eq!(settings.rt_priority, 1);
+        settings.adjust(Setting::RtPriority, 200);
+        assert_eq!(settings.rt_priority, 99);
+
+        // and only the realtime and best-effort classes levels
+        settings.adjust(Setting::IoLevel, 3);
+        assert_eq!(settings.io.level, 0);
+        settings.io.class = IoClass::BestEffort;
+        settings.adjust(Setting::IoLevel, 3);
+        assert_eq!(settings.io.level, 3);
+        settings.adjust(Setting::IoLevel, 10);
+        assert_eq!(settings.io.level, 7);
+        settings.adjust(Setting::IoLevel, -10);
+        assert_eq!(settings.io.level, 0);
+    }
+
+    #[test]
+    fn adjust_cycles_choices() {
+        let mut settings = Settings::default();
+        let mut policies = vec![];
+        for _ in 0..Policy::CHOICES.len() {
+            settings.adjust(Setting::Policy, 1);
+            policies.push(settings.policy.short_name());
+        }
+        assert_eq!(policies, ["B", "IDL", "FF", "RR", "TS"]);
+        // switching to a real-time policy gives it a valid priority, steps
+        // bigger than one still move a single choice
+        settings.adjust(Setting::Policy, -3);
+        assert!(settings.policy == Policy::Rr);
+        assert_eq!(settings.rt_priority, 1);
+        // a policy that cannot be chosen steps from the first one
+        settings.policy = Policy::Deadline;
+        settings.adjust(Setting::Policy, 1);
+        assert!(settings.policy == Policy::Batch);
+
+        let mut classes = vec![];
+        for _ in 0..IoClass::ALL.len() {
+            settings.adjust(Setting::IoClass, -1);
+            classes.push(settings.io.class.name());
+        }
+        assert_eq!(classes, ["idle", "best-effort", "realtime", "none"]);
+    }
+
+    #[test]
+    fn summaries() {
+        let denied = || Err(io::Error::from_raw_os_error(libc::EPERM));
+        assert_eq!(summary(&[(7, Ok(()))]), "Changed the priority of 7");
+        assert_eq!(
+            summary(&[(7, Ok(())), (8, Ok(()))]),
+            "Changed the priority of 2 processes"
+        );
+        assert_eq!(
+            summary(&[(7, denied())]),
+            "Cannot change the priority of 7: Operation not permitted (os error 1)"
+        );
+        assert_eq!(
+            summary(&[(7, Ok(())), (8, denied()), (9, denied())]),
+            "Changed the priority of 1 of 3 processes, 8: Operation not permitted (os error 1)"
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! Collection of the per-process rows shown in the table
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use serde::Serialize;
+use sysinfo::{Pid, Process, System, ThreadKind, MINIMUM_CPU_UPDATE_INTERVAL};
+use users::get_user_by_uid;
+
+use crate::{
+    affinity,
+    column::Column,
+    disk, files,
+    priority::{IoPriority, Policy},
+    threads,
+};
+
+/// Columns filled from `/proc/<pid>/io`, or only when it can be read
+const IO_COLUMNS: [Column; 9] = [
+    Column::ReadRate,
+    Column::WriteRate,
+    Column::ReadBytes,
+    Column::WriteBytes,
+    Column::CancelledWriteBytes,
+    Column::ReadChars,
+    Column::WriteChars,
+    Column::ReadSyscalls,
+    Column::WriteSyscalls,
+];
+
+/// Whether a row is new, gone or unchanged since the previous refresh
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum RowState {
+    #[default]
+    Steady,
+    Added,
+    Removed,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct ProcessMap {
+    pub pid: u32,
+    pub parent: Option<u32>,
+    pub name: String,
+    pub path: String,
+    pub user: String,
+    pub cpu: f32,
+    pub memory: u64,
+    pub virtual_memory: u64,
+    pub memory_percent: f32,
+    /// One letter scheduler state, as in `ps`
+    pub state: String,
+    /// Number of threads of the process, 1 for a thread
+    pub threads: u32,
+    /// CPU the task last ran on
+    pub processor: Option<u32>,
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+    pub nice: i32,
+    /// Scheduling policy, with `rt_priority` for the real-time ones
+    pub policy: Option<Policy>,
+    pub rt_priority: u32,
+    pub io_priority: Option<IoPriority>,
+    /// CPUs the task may run on
+    #[serde(serialize_with = "affinity::serialize")]
+    pub affinity: Option<Vec<usize>>,
+    /// Number of open file descriptors, shared by the threads of a process
+    pub fds: Option<u32>,
+    /// Soft limit on the number of open file descriptors
+    pub fd_limit: Option<u64>,
+    /// Proportional set size, only read while its column is shown
+    pub pss: Option<u64>,
+    /// Unique set size, only read while its column is shown
+    pub uss: Option<u64>,
+    /// Bytes per second read from and written to storage since the last
+    /// refresh, `None` when the I/O counters cannot be read or the refreshes
+    /// were too close to tell
+    pub read_rate: Option<f64>,
+    pub write_rate: Option<f64>,
+    /// The counters of `/proc/<pid>/io`, see `disk::IoCounters`
+    pub rchar: Option<u64>,
+    pub wchar: Option<u64>,
+    pub syscr: Option<u64>,
+    pub syscw: Option<u64>,
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+    pub cancelled_write_bytes: Option<u64>,
+    /// Whether the row is a thread of the process in `parent`
+    pub thread: bool,
+    /// When the task started, in clock ticks since boot, telling it from a
+    /// later task given the same PID
+    #[serde(skip)]
+    pub start_time: Option<u64>,
+    /// Ports the process listens on, only looked up while a filter needs them
+    #[serde(skip)]
+    pub listening: Vec<u16>,
+    #[serde(skip)]
+    pub row_state: RowState,
+}
+
+/// Shown in the path column for kernel threads, which have no executable
+pub const KERNEL_THREAD: &str = "[kthread]";
+/// Shown in the path column when the executable could not be read, usually
+/// because the process belongs to another user
+pub const UNREADABLE: &str = "[unreadable]";
+/// Shown in the user column when the owner of a process is unknown
+pub const UNKNOWN_USER: &str = "?";
+
+/// The processes found by one refresh
+pub struct Snapshot {
+    pub processes: Vec<ProcessMap>,
+    /// Number of (non kernel) processes whose executable could not be read
+    pub inaccessible: usize,
+}
+
+/// Build one `ProcessMap` per process currently known to `sys`, and one per
+/// thread other than the main one if `with_threads` is set.
+///
+/// Fields that cannot be read are filled with placeholders rather than
+/// failing: kernel threads get `KERNEL_THREAD` as their path, processes we
+/// are not allowed to inspect get `UNREADABLE`, UIDs with no matching user
+/// are shown as numbers and non UTF-8 paths are converted lossily.
+///
+/// CPU usage is computed by sysinfo from the difference between the last two
+/// process refreshes, so it is only meaningful once `sys` has been refreshed
+/// twice at least `MINIMUM_CPU_UPDATE_INTERVAL` apart. Disk I/O rates are the
+/// bytes sysinfo saw moving between those refreshes, `interval` apart.
+///
+/// Figures that take reads of their own for every process, such as the open
+/// descriptors, are only read for the `wanted` columns.
+pub fn collect(
+    sys: &System,
+    with_threads: bool,
+    interval: Duration,
+    wanted: &[Column],
+) -> Snapshot {
+    let mut table_process_map = vec![];
+    let mut inaccessible = 0;
+    let mut user_names: HashMap<u32, String> = HashMap::new();
+
+    for (pid, process) in sys.processes() {
+        let thread = process.thread_kind() == Some(ThreadKind::Userland);
+        if thread && !with_threads {
+            continue;
+        }
+        let row = row(sys, *pid, process, &mut user_names, interval, wanted);
+        if row.path == UNREADABLE && !thread {
+            inaccessible += 1;
+        }
+        table_process_map.push(row);
+    }
+
+    Snapshot {
+        processes: table_process_map,
+        inaccessible,
+    }
+}
+
+/// Build the row of one process or thread, caching user names in `user_names`,
+/// with `interval` between the last two refreshes of `sys`, reading the
+/// figures of the `wanted` columns only.
+pub fn row(
+    sys: &System,
+    pid: Pid,
+    process: &Process,
+    user_names: &mut HashMap<u32, String>,
+    interval: Duration,
+    wanted: &[Column],
+) -> ProcessMap {
+    let total_memory = sys.total_memory();
+    let thread = process.thread_kind() == Some(ThreadKind::Userland);
+    let task = match (thread, process.parent()) {
+        (true, Some(parent)) => threads::read_task(parent.as_u32(), pid.as_u32()),
+        _ => threads::read_task(pid.as_u32(), pid.as_u32()),
+    };
+
+    let uid = process.user_id().map(|uid| **uid);
+    let user = match uid {
+        Some(uid) => user_names
+            .entry(uid)
+            .or_insert_with(|| match get_user_by_uid(uid) {
+                Some(user) => user.name().to_string_lossy().into_owned(),
+                None => uid.to_string(),
+            })
+            .clone(),
+        None => String::from(UNKNOWN_USER),
+    };
+
+    // the counters of the process add up those of its threads
+    let io = match (thread, process.parent()) {
+        _ if !IO_COLUMNS.iter().any(|column| wanted.contains(column)) => None,
+        (true, Some(parent)) => disk::read_counters(&format!("/proc/{parent}/task/{pid}/io")),
+        _ => disk::read_counters(&format!("/proc/{pid}/io")),
+    };
+    let usage = process.disk_usage();
+    // over a shorter interval the few bytes counted make wild rates, if any
+    let per_second = |bytes: u64| {
+        (interval >= MINIMUM_CPU_UPDATE_INTERVAL).then(|| bytes as f64 / interval.as_secs_f64())
+    };
+
+    let path = match process.exe() {
+        Some(exe) => exe.to_string_lossy().into_owned(),
+        None if process.thread_kind() == Some(ThreadKind::Kernel) => String::from(KERNEL_THREAD),
+        None => String::from(UNREADABLE),
+    };
+
+    ProcessMap {
+        pid: pid.as_u32(),
+        parent: process.parent().map(|parent| parent.as_u32()),
+        name: String::from(process.name()),
+        path,
+        user,
+        cpu: process.cpu_usage(),
+        memory: process.memory(),
+        virtual_memory: process.virtual_memory(),
+        memory_percent: match total_memory {
+            0 => 0.0,
+            total => (process.memory() as f64 / total as f64 * 100.0) as f32,
+        },
+        state: task.state,
+        threads: match process.tasks() {
+            Some(tasks) if !thread => tasks.len() as u32 + 1,
+            _ => 1,
+        },
+        processor: task.processor,
+        voluntary_switches: task.voluntary_switches,
+        involuntary_switches: task.involuntary_switches,
+        nice: task.nice,
+        policy: task.policy,
+        rt_priority: task.rt_priority,
+        io_priority: task.io_priority,
+        affinity: match wanted.contains(&Column::Affinity) {
+            true => affinity::get(pid.as_u32()).ok(),
+            false => None,
+        },
+        // the limit only marks descriptor counts close to it
+        fds: match thread || !wanted.contains(&Column::Fds) {
+            true => None,
+            false => files::count(pid.as_u32()),
+        },
+        fd_limit: match thread || !wanted.contains(&Column::Fds) {
+            true => None,
+            false => files::limit(pid.as_u32()),
+        },
+        pss: None,
+        uss: None,
+        read_rate: io.and(per_second(usage.read_bytes)),
+        write_rate: io.and(per_second(usage.written_bytes)),
+        rchar: io.map(|io| io.rchar),
+        wchar: io.map(|io| io.wchar),
+        syscr: io.map(|io| io.syscr),
+        syscw: io.map(|io| io.syscw),
+        read_bytes: io.map(|io| io.read_bytes),
+        write_bytes: io.map(|io| io.write_bytes),
+        cancelled_write_bytes: io.map(|io| io.cancelled_write_bytes),
+        thread,
+        start_time: task.start_time,
+        listening: Vec::new(),
+        row_state: RowState::Steady,
+    }
+}
+
+/// Read the nice value, scheduling policy, I/O priority and CPU affinity of a
+/// row again, after they were changed.
+pub fn reread_scheduling(process: &mut ProcessMap) {
+    let task = match (process.thread, process.parent) {
+        (true, Some(parent)) => threads::read_task(parent, process.pid),
+        _ => threads::read_task(process.pid, process.pid),
+    };
+    process.nice = task.nice;
+    process.policy = task.policy;
+    process.rt_priority = task.rt_priority;
+    process.io_priority = task.io_priority;
+    process.affinity = affinity::get(process.pid).ok();
+}
+
+/// Compare a fresh collection against the previous one: processes that were not
+/// there before are marked `Added`, and processes that disappeared are kept for
+/// one more tick marked `Removed`. Rows already marked `Removed` are dropped.
+pub fn mark_changes(previous: &[ProcessMap], current: &mut Vec<ProcessMap>) {
+    if previous.is_empty() {
+        return;
+    }
+
+    let previous_pids: HashSet<u32> = previous
+        .iter()
+        .filter(|p| p.row_state != RowState::Removed)
+        .map(|p| p.pid)
+        .collect();
+    let current_pids: HashSet<u32> = current.iter().map(|p| p.pid).collect();
+
+    for process in current.iter_mut() {
+        if !previous_pids.contains(&process.pid) {
+            process.row_state = RowState::Added;
+        }
+    }
+
+    for process in previous {
+        if process.row_state != RowState::Removed && !current_pids.contains(&process.pid) {
+            let mut gone = process.clone();
+            gone.row_state = RowState::Removed;
+            current.push(gone);
+        }
+    }
+}
+
+/// Order the rows by `column`. Ties are always broken by ascending PID so that
+/// rows with equal keys keep their relative place from one refresh to the next.
+pub fn sort(processes: &mut [ProcessMap], column: Column, descending: bool) {
+    processes.sort_by(|a, b| {
+        let ordering = column.compare(a, b);
+        let ordering = if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering.then(a.pid.cmp(&b.pid))
+    });
+}
+
+/// Format a byte count the way `top` does, e.g. `512K`, `12.3M`, `1.5G`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+
+    if bytes < 1024 {
+        return format!("{bytes}B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    // values that would round up to 1024 move to the next unit
+    while value >= 1023.5 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{value:.1}{}", UNITS[unit])
+    } else {
+        format!("{value:.0}{}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, row_state: RowState) -> ProcessMap {
+        ProcessMap {
+            pid,
+            row_state,
+            ..ProcessMap::default()
+        }
+    }
+
+    fn states(processes: &[ProcessMap]) -> Vec<(u32, &'static str)> {
+        processes
+            .iter()
+            .map(|process| {
+                let state = match process.row_state {
+                    RowState::Steady => "steady",
+                    RowState::Added => "added",
+                    RowState::Removed => "removed",
+                };
+                (process.pid, state)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn first_collection_marks_nothing() {
+        let mut current = vec![process(1, RowState::Steady), process(2, RowState::Steady)];
+        mark_changes(&[], &mut current);
+        assert_eq!(states(&current), [(1, "steady"), (2, "steady")]);
+    }
+
+    #[test]
+    fn marks_started_and_exited_processes() {
+        let previous = [process(1, RowState::Steady), process(2, RowState::Added)];
+        let mut current = vec![process(1, RowState::Steady), process(3, RowState::Steady)];
+        mark_changes(&previous, &mut current);
+        assert_eq!(
+            states(&current),
+            [(1, "steady"), (3, "added"), (2, "removed")]
+        );
+
+        // exited processes are shown for one refresh only, and a PID that
+        // comes back counts as a new process
+        let previous = current;
+        let mut current = vec![process(2, RowState::Steady), process(3, RowState::Steady)];
+        mark_changes(&previous, &mut current);
+        assert_eq!(
+            states(&current),
+            [(2, "added"), (3, "steady"), (1, "removed")]
+        );
+    }
+
+    #[test]
+    fn formats_bytes_like_top() {
+        assert_eq!(format_bytes(0), "0B");
+        assert_eq!(format_bytes(1023), "1023B");
+        assert_eq!(format_bytes(1024), "1.0K");
+        assert_eq!(format_bytes(1536), "1.5K");
+        assert_eq!(format_bytes(10 * 1024), "10K");
+        assert_eq!(format_bytes(512 * 1024), "512K");
+        assert_eq!(format_bytes(1023 * 1024), "1023K");
+        assert_eq!(format_bytes(1024 * 1024 - 1), "1.0M");
+        assert_eq!(format_bytes(12_900_000), "12M");
+        assert_eq!(format_bytes(3 << 30), "3.0G");
+        assert_eq!(format_bytes(5 << 40), "5.0T");
+        assert_eq!(format_bytes(u64::MAX), "16384P");
+    }
+
+    #[test]
+    fn sorts_by_column_then_pid() {
+        let row = |pid, cpu, name: &str| ProcessMap {
+            pid,
+            cpu,
+            name: name.to_string(),
+            ..ProcessMap::default()
+        };
+        let mut processes = vec![
+            row(4, 1.0, "b"),
+            row(2, 5.0, "a"),
+            row(3, 1.0, "c"),
+            row(1, 0.0, "a"),
+        ];
+        let pids = |processes: &[ProcessMap]| -> Vec<u32> {
+            processes.iter().map(|process| process.pid).collect()
+        };
+
+        sort(&mut processes, Column::Cpu, true);
+        assert_eq!(pids(&processes), [2, 3, 4, 1]);
+        // ties stay in ascending PID order whatever the direction
+        sort(&mut processes, Column::Cpu, false);
+        assert_eq!(pids(&processes), [1, 3, 4, 2]);
+        sort(&mut processes, Column::Name, true);
+        assert_eq!(pids(&processes), [3, 4, 1, 2]);
+        sort(&mut processes, Column::Pid, true);
+        assert_eq!(pids(&processes), [4, 3, 2, 1]);
+    }
+}
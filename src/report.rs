@@ -0,0 +1,91 @@
+//! Outcome of an action applied to several processes at once, such as a
+//! signal sent to the tagged processes, process by process
+
+use std::io;
+
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    terminal::Frame,
+    widgets::{Block, BorderType, Clear, Row, Table},
+};
+
+use crate::{popup_area, process::ProcessMap, App};
+
+/// What became of one process
+pub struct Outcome {
+    pub pid: u32,
+    pub name: String,
+    /// Why the action failed, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+/// Name the processes of `results`, listing failures first.
+pub fn outcomes(processes: &[ProcessMap], results: Vec<(u32, io::Result<()>)>) -> Vec<Outcome> {
+    let mut outcomes: Vec<Outcome> = results
+        .into_iter()
+        .map(|(pid, result)| Outcome {
+            pid,
+            name: processes
+                .iter()
+                .find(|process| process.pid == pid)
+                .map(|process| process.name.clone())
+                .unwrap_or_default(),
+            error: result.err().map(|err| err.to_string()),
+        })
+        .collect();
+    // a stable sort, so the processes keep their order otherwise
+    outcomes.sort_by_key(|outcome| outcome.error.is_none());
+    outcomes
+}
+
+pub fn render_report(f: &mut Frame, app: &mut App, area: Rect) {
+    let header = ["PID", "NAME", "RESULT"]
+        .into_iter()
+        .collect::<Row>()
+        .style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .fg(app.colors.header_fg)
+                .bg(app.colors.header_bg),
+        );
+    let rows = app.report.iter().map(|outcome| {
+        let row = Row::new([
+            outcome.pid.to_string(),
+            outcome.name.clone(),
+            outcome
+                .error
+                .clone()
+                .unwrap_or_else(|| String::from("done")),
+        ]);
+        match outcome.error {
+            Some(_) => row.style(Style::new().fg(app.colors.removed_row_fg)),
+            None => row,
+        }
+    });
+    let widths = [
+        Constraint::Length(8),
+        Constraint::Length(16),
+        Constraint::Fill(1),
+    ];
+
+    let area = popup_area(area, 90, app.report.len() as u16 + 3);
+    let table = Table::new(rows, widths)
+        .header(header)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title(app.report_summary.as_str())
+                .title_bottom("(↑/↓) move | (Esc) close"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut app.report_state);
+}
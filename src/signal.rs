@@ -0,0 +1,444 @@
+//! Sending signals to the selected or tagged processes
+
+use std::io;
+
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    terminal::Frame,
+    text::Line,
+    widgets::{Block, BorderType, Clear, List, Paragraph, Wrap},
+};
+
+use crate::{
+    popup_area,
+    process::{ProcessMap, RowState},
+    threads, tree, App,
+};
+
+/// Signals offered in the menu, the last entry asks for a signal number
+pub const SIGNALS: [(&str, i32); 8] = [
+    ("SIGTERM", libc::SIGTERM),
+    ("SIGKILL", libc::SIGKILL),
+    ("SIGSTOP", libc::SIGSTOP),
+    ("SIGCONT", libc::SIGCONT),
+    ("SIGHUP", libc::SIGHUP),
+    ("SIGINT", libc::SIGINT),
+    ("SIGUSR1", libc::SIGUSR1),
+    ("SIGUSR2", libc::SIGUSR2),
+];
+
+/// Which processes a signal is delivered to
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SignalTarget {
+    Process,
+    Subtree,
+    Group,
+}
+
+impl SignalTarget {
+    pub fn next(self) -> Self {
+        match self {
+            SignalTarget::Process => SignalTarget::Subtree,
+            SignalTarget::Subtree => SignalTarget::Group,
+            SignalTarget::Group => SignalTarget::Process,
+        }
+    }
+
+    /// What a signal is sent to with this target, for `processes` chosen ones.
+    fn label(self, processes: usize) -> String {
+        match (self, processes) {
+            (SignalTarget::Process, 1) => String::from("process"),
+            (SignalTarget::Subtree, 1) => String::from("process and its children"),
+            (SignalTarget::Group, 1) => String::from("process group"),
+            (SignalTarget::Process, n) => format!("{n} tagged processes"),
+            (SignalTarget::Subtree, n) => format!("{n} tagged processes and their children"),
+            (SignalTarget::Group, n) => format!("process groups of {n} tagged processes"),
+        }
+    }
+}
+
+/// Name of a signal for display, e.g. `SIGTERM` or `signal 34`.
+pub fn signal_name(signal: i32) -> String {
+    SIGNALS
+        .iter()
+        .find(|(_, number)| *number == signal)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| format!("signal {signal}"))
+}
+
+/// Parse a signal typed by the user, either a number or a name with or
+/// without the `SIG` prefix.
+pub fn parse_signal(input: &str) -> Option<i32> {
+    let input = input.trim();
+    if let Ok(number) = input.parse::<i32>() {
+        return (1..=64).contains(&number).then_some(number);
+    }
+    let upper = input.to_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(known, _)| known[3..] == *name)
+        .map(|(_, number)| *number)
+}
+
+fn kill(pid: u32, signal: i32) -> io::Result<()> {
+    // SAFETY: kill has no memory safety requirements
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn kill_group(pid: u32, signal: i32) -> io::Result<()> {
+    // SAFETY: getpgid and killpg have no memory safety requirements
+    unsafe {
+        let group = libc::getpgid(pid as libc::pid_t);
+        if group < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::killpg(group, signal) == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+/// Whether `pid` is listed in `processes` as exited since the last refresh.
+pub fn exited(processes: &[ProcessMap], pid: u32) -> bool {
+    processes
+        .iter()
+        .any(|process| process.pid == pid && process.row_state == RowState::Removed)
+}
+
+/// Fail with `ESRCH` if `pid` is listed in `processes` as exited, or if the
+/// task now holding it started at another time than the listed one: the PID
+/// was reused and the signal would hit an unrelated process.
+fn check_identity(processes: &[ProcessMap], pid: u32) -> io::Result<()> {
+    let Some(listed) = processes.iter().find(|process| process.pid == pid) else {
+        return Ok(());
+    };
+    let current = match (listed.thread, listed.parent) {
+        (true, Some(parent)) => threads::read_task(parent, pid).start_time,
+        _ => threads::read_task(pid, pid).start_time,
+    };
+    let reused = match (listed.start_time, current) {
+        (Some(listed), Some(current)) => listed != current,
+        // a thread no longer in its process, its TID may be anyone's now
+        (_, None) => listed.thread,
+        (None, Some(_)) => false,
+    };
+    if listed.row_state == RowState::Removed || reused {
+        Err(io::Error::from_raw_os_error(libc::ESRCH))
+    } else {
+        Ok(())
+    }
+}
+
+/// PIDs a signal to `pid` with `target` would be sent to, one by one.
+/// Descendants that have exited are left out.
+pub fn target_pids(processes: &[ProcessMap], pid: u32, target: SignalTarget) -> Vec<u32> {
+    match target {
+        SignalTarget::Process | SignalTarget::Group => vec![pid],
+        SignalTarget::Subtree => {
+            let mut pids = vec![pid];
+            pids.extend(
+                tree::descendants(processes, pid)
+                    .into_iter()
+                    .filter(|&child| !exited(processes, child)),
+            );
+            pids
+        }
+    }
+}
+
+/// Send `signal` to each of `pids` with `target`, giving the outcome by PID.
+/// A process in the subtree of several of them receives it once, and none is
+/// sent to a PID another process took over since the last refresh.
+pub fn deliver(
+    processes: &[ProcessMap],
+    pids: &[u32],
+    signal: i32,
+    target: SignalTarget,
+) -> Vec<(u32, io::Result<()>)> {
+    if target == SignalTarget::Group {
+        return pids
+            .iter()
+            .map(|&pid| {
+                let result = check_identity(processes, pid).and_then(|()| kill_group(pid, signal));
+                (pid, result)
+            })
+            .collect();
+    }
+
+    let mut all: Vec<u32> = vec![];
+    for &pid in pids {
+        for target_pid in target_pids(processes, pid, target) {
+            if !all.contains(&target_pid) {
+                all.push(target_pid);
+            }
+        }
+    }
+    all.into_iter()
+        .map(|pid| {
+            let result = check_identity(processes, pid).and_then(|()| kill(pid, signal));
+            (pid, result)
+        })
+        .collect()
+}
+
+/// Describe what `deliver` did in a sentence for the status line.
+pub fn summary(signal: i32, target: SignalTarget, results: &[(u32, io::Result<()>)]) -> String {
+    let name = signal_name(signal);
+    let failures: Vec<(u32, &io::Error)> = results
+        .iter()
+        .filter_map(|(pid, result)| Some((*pid, result.as_ref().err()?)))
+        .collect();
+
+    match (target, results, failures.as_slice()) {
+        (SignalTarget::Group, [(pid, Ok(()))], _) => {
+            format!("Sent {name} to the process group of {pid}")
+        }
+        (SignalTarget::Group, [(pid, Err(err))], _) => {
+            format!("Failed to send {name} to the process group of {pid}: {err}")
+        }
+        (_, [(pid, _)], []) => format!("Sent {name} to {pid}"),
+        (_, _, []) => format!("Sent {name} to {} processes", results.len()),
+        (_, [_], [(failed, err)]) => format!("Failed to send {name} to {failed}: {err}"),
+        (_, _, [(failed, err), ..]) => format!(
+            "Sent {name} to {} of {} processes, {failed}: {err}",
+            results.len() - failures.len(),
+            results.len()
+        ),
+    }
+}
+
+pub fn render_signal_menu(f: &mut Frame, app: &mut App, area: Rect) {
+    let mut items: Vec<Line> = SIGNALS
+        .iter()
+        .map(|(name, number)| Line::from(format!("{number:>2} {name}")))
+        .collect();
+    items.push(Line::from("   other..."));
+
+    let area = popup_area(area, 40, items.len() as u16 + 4);
+    let menu = List::new(items)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title(format!(
+                    "Send signal to {}",
+                    app.signal_target.label(app.signal_pids.len())
+                ))
+                .title_bottom("(Tab) target | (Enter) select | (Esc) cancel"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(menu, area, &mut app.signal_state);
+}
+
+pub fn render_signal_number(f: &mut Frame, app: &App, area: Rect) {
+    let area = popup_area(area, 40, 3);
+    let prompt = Paragraph::new(Line::from(format!("Signal: {}", app.signal_input)))
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title("Signal number or name"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_widget(prompt, area);
+}
+
+pub fn render_confirm(f: &mut Frame, app: &App, area: Rect) {
+    let Some(signal) = app.pending_signal else {
+        return;
+    };
+    let name = signal_name(signal);
+    let text = match (app.signal_pids.as_slice(), app.signal_target) {
+        ([], _) => return,
+        ([pid], target) => {
+            let process = app
+                .rows
+                .iter()
+                .find(|p| p.pid == *pid)
+                .map(|p| p.name.as_str())
+                .unwrap_or("?");
+            match target {
+                SignalTarget::Process => format!("Send {name} to {pid} ({process})?"),
+                SignalTarget::Subtree => format!(
+                    "Send {name} to {pid} ({process}) and {} descendants?",
+                    target_pids(&app.processes, *pid, SignalTarget::Subtree).len() - 1
+                ),
+                SignalTarget::Group => {
+                    format!("Send {name} to the process group of {pid} ({process})?")
+                }
+            }
+        }
+        (pids, SignalTarget::Subtree) => {
+            let mut all: Vec<u32> = vec![];
+            for &pid in pids {
+                all.extend(target_pids(&app.processes, pid, SignalTarget::Subtree));
+            }
+            all.sort_unstable();
+            all.dedup();
+            format!(
+                "Send {name} to {} tagged processes and {} descendants?",
+                pids.len(),
+                all.len() - pids.len()
+            )
+        }
+        (pids, target) => format!("Send {name} to the {}?", target.label(pids.len())),
+    };
+
+    let area = popup_area(area, 50, 5);
+    let dialog = Paragraph::new(vec![Line::from(text), Line::from("(y) yes | (n) no")])
+        .centered()
+        .wrap(Wrap { trim: true })
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.removed_row_fg))
+                .title("Confirm"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_widget(dialog, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The test process itself, as the table lists it
+    fn this_process(start_time: Option<u64>, row_state: RowState) -> ProcessMap {
+        ProcessMap {
+            pid: std::process::id(),
+            start_time,
+            row_state,
+            ..ProcessMap::default()
+        }
+    }
+
+    fn errno(results: &[(u32, io::Result<()>)]) -> Vec<Option<i32>> {
+        results
+            .iter()
+            .map(|(_, result)| result.as_ref().err().and_then(io::Error::raw_os_error))
+            .collect()
+    }
+
+    #[test]
+    fn parses_signal_names_and_numbers() {
+        assert_eq!(parse_signal("9"), Some(libc::SIGKILL));
+        assert_eq!(parse_signal(" term "), Some(libc::SIGTERM));
+        assert_eq!(parse_signal("SIGusr1"), Some(libc::SIGUSR1));
+        assert_eq!(parse_signal("0"), None);
+        assert_eq!(parse_signal("65"), None);
+        assert_eq!(parse_signal("SIGFOO"), None);
+        assert_eq!(signal_name(libc::SIGHUP), "SIGHUP");
+        assert_eq!(signal_name(34), "signal 34");
+    }
+
+    #[test]
+    fn refuses_exited_and_reused_pids() {
+        let pid = std::process::id();
+        let started = threads::read_task(pid, pid).start_time;
+        assert!(started.is_some());
+
+        // signal 0 only checks that the process can be signalled
+        let send =
+            |process: ProcessMap| errno(&deliver(&[process], &[pid], 0, SignalTarget::Process));
+        assert_eq!(send(this_process(started, RowState::Steady)), [None]);
+        assert_eq!(send(this_process(None, RowState::Steady)), [None]);
+        assert_eq!(
+            send(this_process(started, RowState::Removed)),
+            [Some(libc::ESRCH)]
+        );
+        assert_eq!(
+            send(this_process(
+                started.map(|ticks| ticks + 1),
+                RowState::Steady
+            )),
+            [Some(libc::ESRCH)]
+        );
+    }
+
+    #[test]
+    fn subtrees_leave_out_exited_children() {
+        let process = |pid, parent, row_state| ProcessMap {
+            pid,
+            parent: Some(parent),
+            row_state,
+            ..ProcessMap::default()
+        };
+        let processes = [
+            process(10, 1, RowState::Steady),
+            process(11, 10, RowState::Removed),
+            process(12, 10, RowState::Added),
+            process(13, 12, RowState::Steady),
+        ];
+        assert_eq!(
+            target_pids(&processes, 10, SignalTarget::Subtree),
+            [10, 12, 13]
+        );
+        assert_eq!(target_pids(&processes, 10, SignalTarget::Group), [10]);
+        assert!(exited(&processes, 11));
+        assert!(!exited(&processes, 12));
+    }
+
+    #[test]
+    fn subtrees_leave_out_threads() {
+        let task = |pid, parent, thread| ProcessMap {
+            pid,
+            parent: Some(parent),
+            thread,
+            ..ProcessMap::default()
+        };
+        let processes = [
+            task(10, 1, false),
+            task(20, 10, true),
+            task(21, 10, true),
+            task(30, 10, false),
+            task(31, 30, true),
+        ];
+        assert_eq!(target_pids(&processes, 10, SignalTarget::Subtree), [10, 30]);
+        assert_eq!(target_pids(&processes, 30, SignalTarget::Subtree), [30]);
+    }
+
+    #[test]
+    fn summarizes_outcomes() {
+        let failed = || Err(io::Error::from_raw_os_error(libc::EPERM));
+        let name = signal_name(libc::SIGTERM);
+        let summary =
+            |target, results: &[(u32, io::Result<()>)]| summary(libc::SIGTERM, target, results);
+        assert_eq!(
+            summary(SignalTarget::Process, &[(5, Ok(()))]),
+            format!("Sent {name} to 5")
+        );
+        assert_eq!(
+            summary(SignalTarget::Group, &[(5, Ok(()))]),
+            format!("Sent {name} to the process group of 5")
+        );
+        assert_eq!(
+            summary(SignalTarget::Subtree, &[(5, Ok(())), (6, Ok(()))]),
+            format!("Sent {name} to 2 processes")
+        );
+        assert!(summary(SignalTarget::Process, &[(5, failed())])
+            .starts_with(&format!("Failed to send {name} to 5: ")));
+        assert!(
+            summary(SignalTarget::Process, &[(5, Ok(())), (6, failed())])
+                .starts_with(&format!("Sent {name} to 1 of 2 processes, 6: "))
+        );
+    }
+}
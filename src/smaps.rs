@@ -0,0 +1,470 @@
+//! Where the memory of a process goes, mapping by mapping, from
+//! `/proc/<pid>/smaps`, and its proportional and unique set sizes from
+//! `/proc/<pid>/smaps_rollup`
+
+use std::{cmp::Reverse, collections::BTreeMap, fs, io};
+
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    terminal::Frame,
+    widgets::{Block, BorderType, Clear, Row, Table},
+};
+
+use crate::{popup_area, process::format_bytes, App};
+
+/// Memory figures of a mapping or a group of them, in bytes
+#[derive(Clone, Copy, Default)]
+pub struct Usage {
+    pub size: u64,
+    pub rss: u64,
+    /// Resident memory divided among the processes sharing it
+    pub pss: u64,
+    pub shared_clean: u64,
+    pub shared_dirty: u64,
+    pub private_clean: u64,
+    pub private_dirty: u64,
+    pub swap: u64,
+}
+
+impl Usage {
+    /// Unique set size: the memory freed if the process exited
+    pub fn uss(&self) -> u64 {
+        self.private_clean + self.private_dirty
+    }
+
+    fn add(&mut self, other: &Usage) {
+        self.size += other.size;
+        self.rss += other.rss;
+        self.pss += other.pss;
+        self.shared_clean += other.shared_clean;
+        self.shared_dirty += other.shared_dirty;
+        self.private_clean += other.private_clean;
+        self.private_dirty += other.private_dirty;
+        self.swap += other.swap;
+    }
+
+    /// Take a `Key:  1234 kB` line of `smaps` into account, ignoring others.
+    fn read_field(&mut self, key: &str, value: &str) {
+        let field = match key {
+            "Size" => &mut self.size,
+            "Rss" => &mut self.rss,
+            "Pss" => &mut self.pss,
+            "Shared_Clean" => &mut self.shared_clean,
+            "Shared_Dirty" => &mut self.shared_dirty,
+            "Private_Clean" => &mut self.private_clean,
+            "Private_Dirty" => &mut self.private_dirty,
+            "Swap" => &mut self.swap,
+            _ => return,
+        };
+        let kilobytes: u64 = value
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .unwrap_or(0);
+        *field = kilobytes * 1024;
+    }
+}
+
+/// What backs a mapping
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    File,
+    Heap,
+    Stack,
+    Anon,
+    /// Mappings the kernel provides, such as `[vdso]`
+    Kernel,
+}
+
+impl Kind {
+    fn of(name: &str) -> Self {
+        match name {
+            "[heap]" => Kind::Heap,
+            name if name.starts_with("[stack") => Kind::Stack,
+            name if name.is_empty() || name.starts_with("[anon") => Kind::Anon,
+            name if name.starts_with('[') => Kind::Kernel,
+            _ => Kind::File,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Kind::File => "file backed",
+            Kind::Heap => "heap",
+            Kind::Stack => "stack",
+            Kind::Anon => "anonymous",
+            Kind::Kernel => "kernel",
+        }
+    }
+}
+
+/// One memory mapping of a process
+pub struct Mapping {
+    pub start: u64,
+    /// Permissions as in `/proc/<pid>/maps`, such as `r-xp`
+    pub perms: String,
+    /// The backing file or pseudo path such as `[heap]`, empty when anonymous
+    pub name: String,
+    pub kind: Kind,
+    pub usage: Usage,
+}
+
+/// The mappings of `pid` in address order.
+pub fn read(pid: u32) -> io::Result<Vec<Mapping>> {
+    Ok(parse(&fs::read_to_string(format!("/proc/{pid}/smaps"))?))
+}
+
+/// The mappings listed in the text of an `smaps` file.
+fn parse(smaps: &str) -> Vec<Mapping> {
+    let mut mappings: Vec<Mapping> = vec![];
+    for line in smaps.lines() {
+        let Some((first, rest)) = line.split_once(|c: char| c.is_whitespace()) else {
+            continue;
+        };
+        // field lines start with `Key:`, the others begin a new mapping with
+        // `start-end perms offset device inode [name]`
+        if let Some(key) = first.strip_suffix(':') {
+            if let Some(mapping) = mappings.last_mut() {
+                mapping.usage.read_field(key, rest);
+            }
+            continue;
+        }
+        let Some((start, _)) = first.split_once('-') else {
+            continue;
+        };
+        let mut fields = rest.trim_start().splitn(4, char::is_whitespace);
+        let perms = fields.next().unwrap_or_default().to_string();
+        // the name may contain spaces, it is everything after the inode
+        let name = fields
+            .nth(2)
+            .and_then(|rest| rest.trim_start().split_once(char::is_whitespace))
+            .map(|(_, name)| name.trim().to_string())
+            .unwrap_or_default();
+        mappings.push(Mapping {
+            start: u64::from_str_radix(start, 16).unwrap_or(0),
+            perms,
+            kind: Kind::of(&name),
+            name,
+            usage: Usage::default(),
+        });
+    }
+    mappings
+}
+
+/// The totals of `pid` over all its mappings, which the kernel adds up far
+/// faster than reading `smaps`. `None` for kernel threads and processes that
+/// cannot be read.
+pub fn rollup(pid: u32) -> Option<Usage> {
+    let rollup = fs::read_to_string(format!("/proc/{pid}/smaps_rollup")).ok()?;
+    let mut usage = Usage::default();
+    let mut fields = 0;
+    for (key, value) in rollup.lines().filter_map(|line| line.split_once(':')) {
+        usage.read_field(key, value);
+        fields += 1;
+    }
+    Some(usage).filter(|_| fields > 0)
+}
+
+/// A row of the memory map: the total of a kind, a mapping, or with `merged`
+/// the total of the mappings of one backing file or pseudo path
+struct MapRow {
+    label: String,
+    detail: String,
+    usage: Usage,
+    total: bool,
+}
+
+/// The rows of the memory map: for each kind, its total followed by its
+/// mappings, the most resident first.
+fn map_rows(mappings: &[Mapping], merged: bool) -> Vec<MapRow> {
+    let mut kinds: BTreeMap<Kind, Vec<MapRow>> = BTreeMap::new();
+    if merged {
+        let mut backings: BTreeMap<(Kind, &str), (Usage, usize)> = BTreeMap::new();
+        for mapping in mappings {
+            let backing = backings.entry((mapping.kind, &mapping.name)).or_default();
+            backing.0.add(&mapping.usage);
+            backing.1 += 1;
+        }
+        for ((kind, name), (usage, count)) in backings {
+            kinds.entry(kind).or_default().push(MapRow {
+                label: display_name(name),
+                detail: format!("{count} maps"),
+                usage,
+                total: false,
+            });
+        }
+    } else {
+        for mapping in mappings {
+            kinds.entry(mapping.kind).or_default().push(MapRow {
+                label: display_name(&mapping.name),
+                detail: format!("{:012x} {}", mapping.start, mapping.perms),
+                usage: mapping.usage,
+                total: false,
+            });
+        }
+    }
+
+    let mut rows = vec![];
+    for (kind, mut members) in kinds {
+        let mut usage = Usage::default();
+        for member in &members {
+            usage.add(&member.usage);
+        }
+        members.sort_by_key(|member| Reverse(member.usage.rss));
+        rows.push(MapRow {
+            label: kind.label().to_string(),
+            detail: format!(
+                "{} maps",
+                mappings.iter().filter(|m| m.kind == kind).count()
+            ),
+            usage,
+            total: true,
+        });
+        rows.extend(members.into_iter().map(|mut row| {
+            row.label.insert_str(0, "  ");
+            row
+        }));
+    }
+    rows
+}
+
+/// Cut the start of `text` to fit in `width` columns, the end of a path being
+/// what tells libraries apart.
+fn elide_start(text: &str, width: usize) -> String {
+    let length = text.chars().count();
+    if length <= width || width == 0 {
+        return text.to_string();
+    }
+    let indent = text.len() - text.trim_start().len();
+    let tail: String = text.chars().skip(length - width + 1 + indent).collect();
+    format!("{}…{tail}", &text[..indent])
+}
+
+fn display_name(name: &str) -> String {
+    match name.is_empty() {
+        true => String::from("[anon]"),
+        false => name.to_string(),
+    }
+}
+
+/// Number of rows `render_memory_map` draws, for moving the selection.
+pub fn row_count(app: &App) -> usize {
+    map_rows(&app.mappings, app.merge_mappings).len()
+}
+
+pub fn render_memory_map(f: &mut Frame, app: &mut App, area: Rect) {
+    let header = [
+        "MAPPING", "ADDRESS", "SIZE", "RSS", "PSS", "USS", "SH-CLEAN", "SH-DIRTY", "PR-CLEAN",
+        "PR-DIRTY", "SWAP",
+    ]
+    .into_iter()
+    .collect::<Row>()
+    .style(
+        Style::new()
+            .add_modifier(Modifier::BOLD)
+            .fg(app.colors.header_fg)
+            .bg(app.colors.header_bg),
+    );
+    let area = popup_area(area, area.width.saturating_sub(4), area.height);
+    // what is left of the borders, the fixed columns and the spaces between them
+    let name_width = usize::from(area.width).saturating_sub(2 + 17 + 9 * 8 + 10);
+    let rows = map_rows(&app.mappings, app.merge_mappings)
+        .into_iter()
+        .map(|row| {
+            let usage = row.usage;
+            let cells = Row::new([
+                elide_start(&row.label, name_width),
+                row.detail,
+                format_bytes(usage.size),
+                format_bytes(usage.rss),
+                format_bytes(usage.pss),
+                format_bytes(usage.uss()),
+                format_bytes(usage.shared_clean),
+                format_bytes(usage.shared_dirty),
+                format_bytes(usage.private_clean),
+                format_bytes(usage.private_dirty),
+                format_bytes(usage.swap),
+            ]);
+            match row.total {
+                true => cells.style(Style::new().add_modifier(Modifier::BOLD)),
+                false => cells,
+            }
+        });
+    let mut widths = vec![Constraint::Fill(1), Constraint::Length(17)];
+    widths.extend([Constraint::Length(8); 9]);
+
+    let mut total = Usage::default();
+    for mapping in &app.mappings {
+        total.add(&mapping.usage);
+    }
+    let table = Table::new(rows, widths)
+        .header(header)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title(format!(
+                    "Memory of process {}: RSS {}, PSS {}, USS {}, swap {}",
+                    app.mappings_pid,
+                    format_bytes(total.rss),
+                    format_bytes(total.pss),
+                    format_bytes(total.uss()),
+                    format_bytes(total.swap)
+                ))
+                .title_bottom(match app.merge_mappings {
+                    true => "(↑/↓) move | (m) one row per mapping | (Esc) close",
+                    false => "(↑/↓) move | (m) one row per file | (Esc) close",
+                }),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut app.mappings_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMAPS: &str = "\
+55d0c8e00000-55d0c8e28000 r--p 00000000 08:01 1835100                    /usr/bin/my app
+Size:                160 kB
+Rss:                 160 kB
+Pss:                  80 kB
+Shared_Clean:        160 kB
+Private_Clean:         0 kB
+Swap:                  0 kB
+VmFlags: rd mr mw me dw sd
+55d0c8e28000-55d0c8e9c000 r-xp 00028000 08:01 1835100                    /usr/bin/my app
+Size:                464 kB
+Rss:                 400 kB
+Pss:                 400 kB
+Private_Clean:       400 kB
+55d0c9a4b000-55d0c9a6c000 rw-p 00000000 00:00 0                          [heap]
+Size:                132 kB
+Rss:                  12 kB
+Private_Dirty:        12 kB
+7f3a1c000000-7f3a1c021000 rw-p 00000000 00:00 0 
+Size:                132 kB
+Rss:                   8 kB
+Private_Dirty:         8 kB
+Swap:                  4 kB
+7ffc1e7d9000-7ffc1e7fa000 rw-p 00000000 00:00 0                          [stack]
+Rss:                  16 kB
+7ffc1e7fe000-7ffc1e800000 r-xp 00000000 00:00 0                          [vdso]
+Rss:                   4 kB
+";
+
+    #[test]
+    fn parses_mappings() {
+        let mappings = parse(SMAPS);
+        let summary: Vec<(u64, &str, &str)> = mappings
+            .iter()
+            .map(|m| (m.start, m.perms.as_str(), m.name.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0x55d0c8e00000, "r--p", "/usr/bin/my app"),
+                (0x55d0c8e28000, "r-xp", "/usr/bin/my app"),
+                (0x55d0c9a4b000, "rw-p", "[heap]"),
+                (0x7f3a1c000000, "rw-p", ""),
+                (0x7ffc1e7d9000, "rw-p", "[stack]"),
+                (0x7ffc1e7fe000, "r-xp", "[vdso]"),
+            ]
+        );
+        assert!(mappings[3].kind == Kind::Anon);
+
+        let text = &mappings[0].usage;
+        assert_eq!(text.size, 160 * 1024);
+        assert_eq!(text.pss, 80 * 1024);
+        assert_eq!(text.shared_clean, 160 * 1024);
+        assert_eq!(text.uss(), 0);
+        assert_eq!(mappings[1].usage.uss(), 400 * 1024);
+        assert_eq!(mappings[3].usage.swap, 4 * 1024);
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn reads_fields_in_kilobytes() {
+        let mut usage = Usage::default();
+        usage.read_field("Rss", "      1234 kB");
+        usage.read_field("Private_Dirty", "2 kB");
+        usage.read_field("Swap", "not a number kB");
+        usage.read_field("Locked", "99 kB");
+        assert_eq!(usage.rss, 1234 * 1024);
+        assert_eq!(usage.private_dirty, 2048);
+        assert_eq!(usage.swap, 0);
+        assert_eq!(usage.size, 0);
+    }
+
+    #[test]
+    fn kinds_of_mappings() {
+        assert!(Kind::of("/usr/lib/libc.so.6") == Kind::File);
+        assert!(Kind::of("[heap]") == Kind::Heap);
+        assert!(Kind::of("[stack]") == Kind::Stack);
+        assert!(Kind::of("[stack:1234]") == Kind::Stack);
+        assert!(Kind::of("") == Kind::Anon);
+        assert!(Kind::of("[anon:glibc malloc]") == Kind::Anon);
+        assert!(Kind::of("[vdso]") == Kind::Kernel);
+        assert!(Kind::of("[vvar]") == Kind::Kernel);
+    }
+
+    #[test]
+    fn groups_rows_by_kind() {
+        let labels = |merged| -> Vec<(String, String, u64, bool)> {
+            map_rows(&parse(SMAPS), merged)
+                .into_iter()
+                .map(|row| (row.label, row.detail, row.usage.rss / 1024, row.total))
+                .collect()
+        };
+        let row = |label: &str, detail: &str, rss, total| {
+            (label.to_string(), detail.to_string(), rss, total)
+        };
+
+        assert_eq!(
+            labels(true),
+            [
+                row("file backed", "2 maps", 560, true),
+                row("  /usr/bin/my app", "2 maps", 560, false),
+                row("heap", "1 maps", 12, true),
+                row("  [heap]", "1 maps", 12, false),
+                row("stack", "1 maps", 16, true),
+                row("  [stack]", "1 maps", 16, false),
+                row("anonymous", "1 maps", 8, true),
+                row("  [anon]", "1 maps", 8, false),
+                row("kernel", "1 maps", 4, true),
+                row("  [vdso]", "1 maps", 4, false),
+            ]
+        );
+
+        let unmerged = labels(false);
+        assert_eq!(unmerged.len(), 11);
+        // the most resident mapping first
+        assert_eq!(
+            unmerged[1],
+            row("  /usr/bin/my app", "55d0c8e28000 r-xp", 400, false)
+        );
+        assert_eq!(
+            unmerged[2],
+            row("  /usr/bin/my app", "55d0c8e00000 r--p", 160, false)
+        );
+    }
+
+    #[test]
+    fn elides_the_start_of_long_names() {
+        assert_eq!(elide_start("/usr/lib/libc.so.6", 18), "/usr/lib/libc.so.6");
+        assert_eq!(elide_start("/usr/lib/libc.so.6", 10), "…libc.so.6");
+        // the indent is kept and counts in the width
+        assert_eq!(elide_start("  /usr/lib/libc.so.6", 10), "  …bc.so.6");
+        assert_eq!(elide_start("/é/ü/libc.so.6", 6), "….so.6");
+        assert_eq!(elide_start("/usr/lib/libc.so.6", 0), "/usr/lib/libc.so.6");
+    }
+}
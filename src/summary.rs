@@ -0,0 +1,263 @@
+//! System overview shown above the process table
+
+use std::time::Instant;
+
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style, Stylize},
+    terminal::Frame,
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+};
+use sysinfo::{Disks, ProcessStatus, System, ThreadKind};
+
+use crate::{disk, process::format_bytes, App};
+
+/// Number of lines below the CPU bars: memory, swap, disk and the text line
+const EXTRA_LINES: u16 = 4;
+
+#[derive(Default)]
+pub struct TaskCounts {
+    pub total: usize,
+    pub threads: usize,
+    pub kernel_threads: usize,
+    pub running: usize,
+    pub sleeping: usize,
+    pub stopped: usize,
+    pub zombie: usize,
+}
+
+pub struct Summary {
+    pub cpus: Vec<f32>,
+    pub total_memory: u64,
+    pub used_memory: u64,
+    pub total_swap: u64,
+    pub used_swap: u64,
+    pub load_average: (f64, f64, f64),
+    pub uptime: u64,
+    pub tasks: TaskCounts,
+    pub host_name: String,
+    pub kernel_version: String,
+    /// Mounted disks, telling which devices of `/proc/diskstats` to add up
+    disks: Disks,
+    /// Kernel names of the devices the disks are mounted from
+    pub disk_devices: Vec<String>,
+    /// Bytes read and written by those devices at the last update, and when
+    disk_totals: Option<(u64, u64, Instant)>,
+    /// Bytes per second read from and written to them since the update before
+    pub disk_read_rate: f64,
+    pub disk_write_rate: f64,
+}
+
+impl Summary {
+    pub fn new() -> Self {
+        Self {
+            cpus: Vec::new(),
+            total_memory: 0,
+            used_memory: 0,
+            total_swap: 0,
+            used_swap: 0,
+            load_average: (0.0, 0.0, 0.0),
+            uptime: 0,
+            tasks: TaskCounts::default(),
+            host_name: System::host_name().unwrap_or_else(|| String::from("unknown")),
+            kernel_version: System::kernel_version().unwrap_or_else(|| String::from("unknown")),
+            disks: Disks::new(),
+            disk_devices: Vec::new(),
+            disk_totals: None,
+            disk_read_rate: 0.0,
+            disk_write_rate: 0.0,
+        }
+    }
+
+    /// Read the figures again from `sys`, which must have had its CPU, memory
+    /// and process information refreshed.
+    pub fn update(&mut self, sys: &System) {
+        self.cpus = sys.cpus().iter().map(|cpu| cpu.cpu_usage()).collect();
+        self.total_memory = sys.total_memory();
+        self.used_memory = sys.used_memory();
+        self.total_swap = sys.total_swap();
+        self.used_swap = sys.used_swap();
+
+        let load = System::load_average();
+        self.load_average = (load.one, load.five, load.fifteen);
+        self.uptime = System::uptime();
+
+        let mut tasks = TaskCounts::default();
+        for process in sys.processes().values() {
+            match process.thread_kind() {
+                Some(ThreadKind::Userland) => {
+                    tasks.threads += 1;
+                    continue;
+                }
+                Some(ThreadKind::Kernel) => tasks.kernel_threads += 1,
+                None => tasks.total += 1,
+            }
+            match process.status() {
+                ProcessStatus::Run => tasks.running += 1,
+                ProcessStatus::Stop | ProcessStatus::Tracing => tasks.stopped += 1,
+                ProcessStatus::Zombie => tasks.zombie += 1,
+                _ => tasks.sleeping += 1,
+            }
+        }
+        self.tasks = tasks;
+
+        // disks come and go, so the mounts are read again each time
+        self.disks.refresh_list();
+        self.disk_devices = disk::mounted_devices(&self.disks);
+        let now = Instant::now();
+        let totals = disk::device_totals(&self.disk_devices);
+        if let (Some((read, written)), Some((last_read, last_written, last))) =
+            (totals, self.disk_totals)
+        {
+            let seconds = now.duration_since(last).as_secs_f64();
+            self.disk_read_rate = read.saturating_sub(last_read) as f64 / seconds;
+            self.disk_write_rate = written.saturating_sub(last_written) as f64 / seconds;
+        }
+        self.disk_totals = totals.map(|(read, written)| (read, written, now));
+    }
+
+    /// Height the header needs, with the CPU bars laid out in two columns.
+    pub fn height(&self) -> u16 {
+        self.cpus.len().div_ceil(2) as u16 + EXTRA_LINES
+    }
+}
+
+/// Format seconds as `3 days, 04:05:06`.
+pub fn format_uptime(seconds: u64) -> String {
+    let days = seconds / 86_400;
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        seconds % 86_400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    );
+    match days {
+        0 => time,
+        1 => format!("1 day, {time}"),
+        _ => format!("{days} days, {time}"),
+    }
+}
+
+/// A `label[|||||     text]` gauge filling `width` columns.
+fn bar<'a>(label: String, ratio: f64, text: String, width: u16, color: Color) -> Line<'a> {
+    let inner = (width as usize).saturating_sub(label.len() + 2);
+    let filled = ((ratio.clamp(0.0, 1.0) * inner as f64).round() as usize)
+        .min(inner.saturating_sub(text.len()));
+    let padding = inner.saturating_sub(filled + text.len());
+
+    Line::from(vec![
+        Span::raw(label).bold(),
+        Span::raw("["),
+        Span::raw("|".repeat(filled)).fg(color),
+        Span::raw(" ".repeat(padding)),
+        Span::raw(text),
+        Span::raw("]"),
+    ])
+}
+
+pub fn render_summary(f: &mut Frame, app: &App, area: Rect) {
+    let summary = &app.summary;
+    let [cpu_area, memory_area, swap_area, disk_area, info_area] = Layout::vertical([
+        Constraint::Length(summary.cpus.len().div_ceil(2) as u16),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(area);
+    f.render_widget(
+        Block::new().style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg)),
+        area,
+    );
+
+    let columns = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .spacing(1)
+        .split(cpu_area);
+    let per_column = summary.cpus.len().div_ceil(2);
+    let label_width = summary.cpus.len().to_string().len();
+    for (column, chunk) in summary.cpus.chunks(per_column.max(1)).enumerate() {
+        let lines: Vec<Line> = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, usage)| {
+                bar(
+                    format!("{:>label_width$}", column * per_column + i),
+                    *usage as f64 / 100.0,
+                    format!("{usage:.1}%"),
+                    columns[column].width,
+                    app.colors.added_row_fg,
+                )
+            })
+            .collect();
+        f.render_widget(Paragraph::new(lines), columns[column]);
+    }
+
+    let ratio = |used: u64, total: u64| match total {
+        0 => 0.0,
+        total => used as f64 / total as f64,
+    };
+    f.render_widget(
+        Paragraph::new(bar(
+            String::from("Mem"),
+            ratio(summary.used_memory, summary.total_memory),
+            format!(
+                "{}/{}",
+                format_bytes(summary.used_memory),
+                format_bytes(summary.total_memory)
+            ),
+            memory_area.width,
+            app.colors.selected_style_fg,
+        )),
+        memory_area,
+    );
+    f.render_widget(
+        Paragraph::new(bar(
+            String::from("Swp"),
+            ratio(summary.used_swap, summary.total_swap),
+            format!(
+                "{}/{}",
+                format_bytes(summary.used_swap),
+                format_bytes(summary.total_swap)
+            ),
+            swap_area.width,
+            app.colors.removed_row_fg,
+        )),
+        swap_area,
+    );
+
+    let disk = match summary.disk_devices.is_empty() {
+        true => String::from("no block device mounted"),
+        false => format!(
+            "read {}/s, write {}/s ({})",
+            format_bytes(summary.disk_read_rate as u64),
+            format_bytes(summary.disk_write_rate as u64),
+            summary.disk_devices.join(", ")
+        ),
+    };
+    f.render_widget(
+        Paragraph::new(Line::from(vec![Span::raw("Dsk ").bold(), Span::raw(disk)]))
+            .style(Style::new().fg(app.colors.row_fg)),
+        disk_area,
+    );
+
+    let tasks = &summary.tasks;
+    let (one, five, fifteen) = summary.load_average;
+    let info = format!(
+        "Tasks: {}, {} thr, {} kthr; {} running, {} sleeping, {} stopped, {} zombie | Load: {one:.2} {five:.2} {fifteen:.2} | Uptime: {} | {} ({})",
+        tasks.total,
+        tasks.threads,
+        tasks.kernel_threads,
+        tasks.running,
+        tasks.sleeping,
+        tasks.stopped,
+        tasks.zombie,
+        format_uptime(summary.uptime),
+        summary.host_name,
+        summary.kernel_version,
+    );
+    f.render_widget(
+        Paragraph::new(Line::from(info)).style(Style::new().fg(app.colors.row_fg)),
+        info_area,
+    );
+}
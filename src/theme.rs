@@ -0,0 +1,181 @@
+//! Color themes of the interface: the built-in palettes, a monochrome and a
+//! high contrast theme, and themes defined in the config file
+
+use std::{env, str::FromStr};
+
+use ratatui::style::{palette::tailwind, Color};
+use serde::{Deserialize, Serialize};
+
+/// Colors of every widget, from the current theme
+#[derive(Clone, Copy)]
+pub struct TableColors {
+    pub buffer_bg: Color,
+    pub header_bg: Color,
+    pub header_fg: Color,
+    pub row_fg: Color,
+    pub selected_style_fg: Color,
+    pub normal_row_color: Color,
+    pub alt_row_color: Color,
+    pub added_row_fg: Color,
+    pub removed_row_fg: Color,
+    pub footer_border_color: Color,
+}
+
+impl TableColors {
+    const fn new(color: &tailwind::Palette) -> Self {
+        Self {
+            buffer_bg: tailwind::SLATE.c950,
+            header_bg: color.c900,
+            header_fg: tailwind::SLATE.c200,
+            row_fg: tailwind::SLATE.c200,
+            selected_style_fg: color.c400,
+            normal_row_color: tailwind::SLATE.c950,
+            alt_row_color: tailwind::SLATE.c900,
+            added_row_fg: tailwind::GREEN.c400,
+            removed_row_fg: tailwind::RED.c400,
+            footer_border_color: color.c400,
+        }
+    }
+
+    /// The terminal's own colors everywhere, rows being told apart by bold,
+    /// dim and reversed text instead.
+    const fn monochrome() -> Self {
+        Self {
+            buffer_bg: Color::Reset,
+            header_bg: Color::Reset,
+            header_fg: Color::Reset,
+            row_fg: Color::Reset,
+            selected_style_fg: Color::Reset,
+            normal_row_color: Color::Reset,
+            alt_row_color: Color::Reset,
+            added_row_fg: Color::Reset,
+            removed_row_fg: Color::Reset,
+            footer_border_color: Color::Reset,
+        }
+    }
+
+    /// Bright basic colors on black, readable on any terminal.
+    const fn high_contrast() -> Self {
+        Self {
+            buffer_bg: Color::Black,
+            header_bg: Color::White,
+            header_fg: Color::Black,
+            row_fg: Color::White,
+            selected_style_fg: Color::LightYellow,
+            normal_row_color: Color::Black,
+            alt_row_color: Color::Black,
+            added_row_fg: Color::LightGreen,
+            removed_row_fg: Color::LightRed,
+            footer_border_color: Color::White,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub colors: TableColors,
+}
+
+/// Name of the theme used when `NO_COLOR` is set
+pub const MONOCHROME: &str = "mono";
+
+/// The built-in themes, in the order the palette key cycles through them.
+pub fn builtin() -> Vec<Theme> {
+    let theme = |name: &str, colors| Theme {
+        name: name.to_string(),
+        colors,
+    };
+    vec![
+        theme("blue", TableColors::new(&tailwind::BLUE)),
+        theme("emerald", TableColors::new(&tailwind::EMERALD)),
+        theme("indigo", TableColors::new(&tailwind::INDIGO)),
+        theme("red", TableColors::new(&tailwind::RED)),
+        theme("high-contrast", TableColors::high_contrast()),
+        theme(MONOCHROME, TableColors::monochrome()),
+    ]
+}
+
+/// Index of the theme called `name`, ignoring case. A theme from the config
+/// file hides a built-in one of the same name.
+pub fn find(themes: &[Theme], name: &str) -> Option<usize> {
+    themes
+        .iter()
+        .rposition(|theme| theme.name.eq_ignore_ascii_case(name))
+}
+
+/// Whether the user asked for no colors, see <https://no-color.org>.
+pub fn no_color() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// A theme in the config file. Colors are names such as `red` or
+/// `light-blue`, `#rrggbb` or a 256-color index, and the ones left out are
+/// taken from the `base` theme, `blue` by default.
+///
+/// ```toml
+/// [themes.solarized]
+/// base = "high-contrast"
+/// buffer_bg = "#002b36"
+/// row_fg = "#839496"
+/// ```
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_bg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_bg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row_fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selected_style_fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal_row_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt_row_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub added_row_fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed_row_fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer_border_color: Option<String>,
+}
+
+impl ThemeConfig {
+    /// Build the theme called `name`, looking its base up in `themes`.
+    pub fn build(&self, name: &str, themes: &[Theme]) -> Result<Theme, String> {
+        let base = self.base.as_deref().unwrap_or("blue");
+        let mut colors = find(themes, base)
+            .map(|i| themes[i].colors)
+            .ok_or_else(|| format!("theme '{name}': unknown base theme '{base}'"))?;
+
+        let fields = [
+            (&self.buffer_bg, &mut colors.buffer_bg),
+            (&self.header_bg, &mut colors.header_bg),
+            (&self.header_fg, &mut colors.header_fg),
+            (&self.row_fg, &mut colors.row_fg),
+            (&self.selected_style_fg, &mut colors.selected_style_fg),
+            (&self.normal_row_color, &mut colors.normal_row_color),
+            (&self.alt_row_color, &mut colors.alt_row_color),
+            (&self.added_row_fg, &mut colors.added_row_fg),
+            (&self.removed_row_fg, &mut colors.removed_row_fg),
+            (&self.footer_border_color, &mut colors.footer_border_color),
+        ];
+        for (text, color) in fields {
+            if let Some(text) = text {
+                *color = Color::from_str(text)
+                    .map_err(|_| format!("theme '{name}': unknown color '{text}'"))?;
+            }
+        }
+
+        Ok(Theme {
+            name: name.to_string(),
+            colors,
+        })
+    }
+}
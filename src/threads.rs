@@ -0,0 +1,269 @@
+//! Threads of a process, from `/proc/<pid>/task`
+
+use std::{collections::HashMap, fs, time::Duration};
+
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    terminal::Frame,
+    widgets::{Block, BorderType, Clear, Row, Table},
+};
+use sysinfo::{Pid, System};
+
+use crate::{
+    column::Column,
+    popup_area,
+    priority::{self, IoPriority, Policy},
+    process::{self, ProcessMap},
+    App,
+};
+
+/// Columns of the thread list
+const COLUMNS: [Column; 7] = [
+    Column::Pid,
+    Column::Name,
+    Column::State,
+    Column::Cpu,
+    Column::Processor,
+    Column::VoluntarySwitches,
+    Column::InvoluntarySwitches,
+];
+
+/// Scheduler figures of one task that sysinfo does not provide
+#[derive(Default)]
+pub struct TaskStat {
+    pub state: String,
+    pub processor: Option<u32>,
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+    pub nice: i32,
+    pub policy: Option<Policy>,
+    pub rt_priority: u32,
+    pub io_priority: Option<IoPriority>,
+    /// When the task started, in clock ticks since boot
+    pub start_time: Option<u64>,
+}
+
+/// Read the figures of thread `tid` of process `pid`, the main thread having
+/// the PID as TID. Files that cannot be read leave their fields empty.
+pub fn read_task(pid: u32, tid: u32) -> TaskStat {
+    let dir = format!("/proc/{pid}/task/{tid}");
+    let mut task = TaskStat::default();
+
+    if let Ok(stat) = fs::read_to_string(format!("{dir}/stat")) {
+        parse_stat(&stat, &mut task);
+    }
+    task.io_priority = priority::io_priority(tid);
+    if let Ok(status) = fs::read_to_string(format!("{dir}/status")) {
+        parse_status(&status, &mut task);
+    }
+
+    task
+}
+
+/// Fill in the figures of `task` found in the text of its `stat` file.
+fn parse_stat(stat: &str, task: &mut TaskStat) {
+    // the command name may contain spaces and parentheses, the state is the
+    // first field after it, the nice value the 17th, the start time the 20th,
+    // the processor the 37th, the real-time priority the 38th and the policy
+    // the 39th
+    let Some((_, fields)) = stat.rsplit_once(')') else {
+        return;
+    };
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    task.state = fields.first().unwrap_or(&"").to_string();
+    task.nice = fields
+        .get(16)
+        .and_then(|nice| nice.parse().ok())
+        .unwrap_or(0);
+    task.start_time = fields.get(19).and_then(|start| start.parse().ok());
+    task.processor = fields.get(36).and_then(|cpu| cpu.parse().ok());
+    task.rt_priority = fields
+        .get(37)
+        .and_then(|priority| priority.parse().ok())
+        .unwrap_or(0);
+    task.policy = fields
+        .get(38)
+        .and_then(|policy| policy.parse().ok())
+        .and_then(Policy::from_raw);
+}
+
+/// Fill in the context switches of `task` found in the text of its `status`
+/// file.
+fn parse_status(status: &str, task: &mut TaskStat) {
+    for (key, value) in status.lines().filter_map(|line| line.split_once(':')) {
+        match key {
+            "voluntary_ctxt_switches" => {
+                task.voluntary_switches = value.trim().parse().unwrap_or(0)
+            }
+            "nonvoluntary_ctxt_switches" => {
+                task.involuntary_switches = value.trim().parse().unwrap_or(0)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The TIDs of the threads of `pid`, the main one included, or just `pid`
+/// if they cannot be listed.
+pub fn tids(pid: u32) -> Vec<u32> {
+    match fs::read_dir(format!("/proc/{pid}/task")) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .collect(),
+        Err(_) => vec![pid],
+    }
+}
+
+/// One row per thread of `pid`, the busiest first, or `None` if the process
+/// has exited.
+///
+/// sysinfo lists the threads other than the main one with their own CPU
+/// usage, while the entry of the process itself adds up all of them. The
+/// main thread gets what is left once the other threads are taken out.
+pub fn list(sys: &System, pid: u32, interval: Duration) -> Option<Vec<ProcessMap>> {
+    let process = sys.process(Pid::from_u32(pid))?;
+    let mut user_names = HashMap::new();
+
+    let mut rows = vec![];
+    let mut others = 0.0;
+    for tid in tids(pid).into_iter().filter(|&tid| tid != pid) {
+        // a thread started since the last refresh is not known to sysinfo yet
+        if let Some(thread) = sys.process(Pid::from_u32(tid)) {
+            let row = process::row(
+                sys,
+                Pid::from_u32(tid),
+                thread,
+                &mut user_names,
+                interval,
+                &COLUMNS,
+            );
+            others += row.cpu;
+            rows.push(row);
+        }
+    }
+    let mut main = process::row(
+        sys,
+        Pid::from_u32(pid),
+        process,
+        &mut user_names,
+        interval,
+        &COLUMNS,
+    );
+    main.cpu = main_thread_cpu(main.cpu, others);
+    main.threads = 1;
+    rows.push(main);
+
+    process::sort(&mut rows, Column::Cpu, true);
+    Some(rows)
+}
+
+/// The CPU usage of the main thread, given that of the whole process and the
+/// sum of the other threads. The two are sampled at slightly different times,
+/// so the other threads can seem to use more than the process.
+fn main_thread_cpu(process: f32, others: f32) -> f32 {
+    (process - others).max(0.0)
+}
+
+pub fn render_threads(f: &mut Frame, app: &mut App, area: Rect) {
+    let header = COLUMNS
+        .iter()
+        .map(|column| match column {
+            Column::Pid => "TID",
+            column => column.label(),
+        })
+        .collect::<Row>()
+        .style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .fg(app.colors.header_fg)
+                .bg(app.colors.header_bg),
+        );
+    let rows = app
+        .thread_rows
+        .iter()
+        .map(|thread| Row::new(COLUMNS.iter().map(|column| column.cell(thread))));
+    let widths = COLUMNS.iter().map(|column| match column {
+        Column::Name => Constraint::Fill(1),
+        column => Constraint::Length(column.min_width().max(7)),
+    });
+
+    let area = popup_area(area, 90, area.height);
+    let table = Table::new(rows, widths)
+        .header(header)
+        .style(Style::new().fg(app.colors.row_fg).bg(app.colors.buffer_bg))
+        .highlight_style(
+            Style::new()
+                .add_modifier(Modifier::REVERSED)
+                .fg(app.colors.selected_style_fg),
+        )
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .border_style(Style::new().fg(app.colors.footer_border_color))
+                .title(format!(
+                    "{} threads of process {}",
+                    app.thread_rows.len(),
+                    app.thread_pid
+                ))
+                .title_bottom("(↑/↓) move | (Esc) close"),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut app.thread_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_stat_fields() {
+        // a name with spaces and parentheses, nice -5, started at tick 4242,
+        // last on CPU 3, SCHED_FIFO with priority 10
+        let stat = "4321 (Web (Content) 1) R 1 4321 4321 0 -1 4194560 0 0 0 0 7 3 0 0 -11 -5 \
+                    12 0 4242 1000 200 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 0 17 3 10 1 \
+                    0 0 0 0 0 0 0 0 0 0 0";
+        let mut task = TaskStat::default();
+        parse_stat(stat, &mut task);
+        assert_eq!(task.state, "R");
+        assert_eq!(task.nice, -5);
+        assert_eq!(task.start_time, Some(4242));
+        assert_eq!(task.processor, Some(3));
+        assert_eq!(task.rt_priority, 10);
+        assert!(task.policy == Some(Policy::Fifo));
+    }
+
+    #[test]
+    fn parses_truncated_stat() {
+        let mut task = TaskStat::default();
+        parse_stat("4321 (sh) S 1 4321", &mut task);
+        assert_eq!(task.state, "S");
+        assert_eq!(task.nice, 0);
+        assert_eq!(task.start_time, None);
+        assert_eq!(task.processor, None);
+        assert!(task.policy.is_none());
+
+        let mut task = TaskStat::default();
+        parse_stat("no name", &mut task);
+        assert_eq!(task.state, "");
+    }
+
+    #[test]
+    fn parses_context_switches() {
+        let status = "Name:\tsh\nState:\tS (sleeping)\n\
+                      voluntary_ctxt_switches:\t150\nnonvoluntary_ctxt_switches:\t 7\n";
+        let mut task = TaskStat::default();
+        parse_status(status, &mut task);
+        assert_eq!(task.voluntary_switches, 150);
+        assert_eq!(task.involuntary_switches, 7);
+    }
+
+    #[test]
+    fn main_thread_gets_the_rest() {
+        assert_eq!(main_thread_cpu(50.0, 20.0), 30.0);
+        assert_eq!(main_thread_cpu(20.0, 20.0), 0.0);
+        assert_eq!(main_thread_cpu(20.0, 20.5), 0.0);
+        assert_eq!(main_thread_cpu(0.0, 100.0), 0.0);
+    }
+}
@@ -0,0 +1,237 @@
+//! Arrange processes as a tree following their parent PIDs
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::process::ProcessMap;
+
+/// A process placed in the tree, in display order
+pub struct TreeRow {
+    /// The process, with the totals of its whole subtree if it is collapsed
+    pub process: ProcessMap,
+    /// Guide glyphs to draw in front of the name
+    pub prefix: String,
+    pub has_children: bool,
+}
+
+/// Flatten `processes` into depth-first tree order. Children keep the relative
+/// order they have in `processes`, so sort the slice first to sort siblings.
+/// Descendants of PIDs in `collapsed` are hidden and the CPU and memory of the
+/// processes among them are added to the collapsed process instead.
+pub fn flatten(processes: &[ProcessMap], collapsed: &HashSet<u32>) -> Vec<TreeRow> {
+    let known: HashSet<u32> = processes.iter().map(|p| p.pid).collect();
+    let mut children: HashMap<u32, Vec<usize>> = HashMap::new();
+    let mut roots = vec![];
+
+    for (i, process) in processes.iter().enumerate() {
+        match process.parent {
+            Some(parent) if parent != process.pid && known.contains(&parent) => {
+                children.entry(parent).or_default().push(i)
+            }
+            _ => roots.push(i),
+        }
+    }
+
+    let mut rows = vec![];
+    let mut visited = HashSet::new();
+    for &root in &roots {
+        push_subtree(
+            processes,
+            &children,
+            collapsed,
+            root,
+            "",
+            None,
+            &mut visited,
+            &mut rows,
+        );
+    }
+    rows
+}
+
+/// Append the row for `processes[index]` and, unless it is collapsed, the rows
+/// of its descendants. `is_last` is `None` for roots, otherwise whether the node
+/// is the last of its siblings.
+#[allow(clippy::too_many_arguments)]
+fn push_subtree(
+    processes: &[ProcessMap],
+    children: &HashMap<u32, Vec<usize>>,
+    collapsed: &HashSet<u32>,
+    index: usize,
+    indent: &str,
+    is_last: Option<bool>,
+    visited: &mut HashSet<u32>,
+    rows: &mut Vec<TreeRow>,
+) {
+    let process = &processes[index];
+    // PIDs get reused, so guard against a parent chain that loops back
+    if !visited.insert(process.pid) {
+        return;
+    }
+
+    let kids = children.get(&process.pid).map(Vec::as_slice).unwrap_or(&[]);
+    let is_collapsed = !kids.is_empty() && collapsed.contains(&process.pid);
+
+    let connector = match is_last {
+        None => "",
+        Some(false) => "├─",
+        Some(true) => "└─",
+    };
+    let marker = match (kids.is_empty(), is_collapsed) {
+        (true, _) => " ",
+        (false, true) => "+",
+        (false, false) => "-",
+    };
+
+    let mut row = process.clone();
+    if is_collapsed {
+        let mut stack: Vec<usize> = kids.to_vec();
+        let mut seen = HashSet::from([process.pid]);
+        while let Some(i) = stack.pop() {
+            let child = &processes[i];
+            if !seen.insert(child.pid) {
+                continue;
+            }
+            // thread rows repeat part of their process's usage
+            if !child.thread {
+                row.cpu += child.cpu;
+                row.memory += child.memory;
+                row.virtual_memory += child.virtual_memory;
+                row.memory_percent += child.memory_percent;
+            }
+            if let Some(grandchildren) = children.get(&child.pid) {
+                stack.extend(grandchildren);
+            }
+        }
+    }
+
+    rows.push(TreeRow {
+        process: row,
+        prefix: format!("{indent}{connector}{marker} "),
+        has_children: !kids.is_empty(),
+    });
+
+    if is_collapsed {
+        return;
+    }
+
+    let child_indent = match is_last {
+        None => indent.to_string(),
+        Some(false) => format!("{indent}│ "),
+        Some(true) => format!("{indent}  "),
+    };
+    for (n, &child) in kids.iter().enumerate() {
+        push_subtree(
+            processes,
+            children,
+            collapsed,
+            child,
+            &child_indent,
+            Some(n == kids.len() - 1),
+            visited,
+            rows,
+        );
+    }
+}
+
+/// PIDs of every descendant of `pid`, children before grandchildren. Thread
+/// rows are left out: a signal to a thread ID reaches its whole process.
+pub fn descendants(processes: &[ProcessMap], pid: u32) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for process in processes.iter().filter(|process| !process.thread) {
+        if let Some(parent) = process.parent {
+            if parent != process.pid {
+                children.entry(parent).or_default().push(process.pid);
+            }
+        }
+    }
+
+    let mut found = vec![];
+    let mut seen = HashSet::from([pid]);
+    let mut queue = VecDeque::from([pid]);
+    while let Some(current) = queue.pop_front() {
+        for &child in children.get(&current).map(Vec::as_slice).unwrap_or(&[]) {
+            if seen.insert(child) {
+                found.push(child);
+                queue.push_back(child);
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, parent: Option<u32>, cpu: f32, thread: bool) -> ProcessMap {
+        ProcessMap {
+            pid,
+            parent,
+            cpu,
+            memory: 100,
+            thread,
+            ..ProcessMap::default()
+        }
+    }
+
+    fn tree() -> Vec<ProcessMap> {
+        vec![
+            process(1, None, 1.0, false),
+            process(2, Some(1), 2.0, false),
+            process(3, Some(2), 4.0, false),
+            // a thread of 3, which shares its usage
+            process(4, Some(3), 4.0, true),
+            process(5, Some(1), 8.0, false),
+        ]
+    }
+
+    #[test]
+    fn flattens_depth_first() {
+        let rows = flatten(&tree(), &HashSet::new());
+        let order: Vec<(u32, &str)> = rows
+            .iter()
+            .map(|row| (row.process.pid, row.prefix.as_str()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (1, "- "),
+                (2, "├─- "),
+                (3, "│ └─- "),
+                (4, "│   └─  "),
+                (5, "└─  "),
+            ]
+        );
+    }
+
+    #[test]
+    fn collapsed_nodes_add_up_processes_but_not_threads() {
+        let rows = flatten(&tree(), &HashSet::from([2]));
+        let pids: Vec<u32> = rows.iter().map(|row| row.process.pid).collect();
+        assert_eq!(pids, [1, 2, 5]);
+        assert_eq!(rows[1].prefix, "├─+ ");
+        assert_eq!(rows[1].process.cpu, 6.0);
+        assert_eq!(rows[1].process.memory, 200);
+
+        let rows = flatten(&tree(), &HashSet::from([1]));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].process.cpu, 15.0);
+    }
+
+    #[test]
+    fn descendants_survive_parent_loops() {
+        let processes = [
+            process(1, Some(2), 0.0, false),
+            process(2, Some(1), 0.0, false),
+        ];
+        assert_eq!(descendants(&processes, 1), [2]);
+    }
+
+    #[test]
+    fn lists_descendants_breadth_first() {
+        // 4 is a thread of 3
+        assert_eq!(descendants(&tree(), 1), [2, 5, 3]);
+        assert_eq!(descendants(&tree(), 3), Vec::<u32>::new());
+        assert_eq!(descendants(&tree(), 5), Vec::<u32>::new());
+    }
+}
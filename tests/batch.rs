@@ -0,0 +1,93 @@
+//! Batch mode as a user runs it: the binary printing to a pipe
+
+use std::{
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::Mutex,
+};
+
+/// Held by each test while rustytop runs: on a machine with few CPUs, another
+/// rustytop sampling every process can starve the spinner of the CPU during the
+/// short first interval.
+static RUNNING: Mutex<()> = Mutex::new(());
+
+/// A shell spinning on a CPU until dropped
+struct Spinner(Child);
+
+impl Spinner {
+    fn start() -> Self {
+        let child = Command::new("sh")
+            .args(["-c", "while :; do :; done"])
+            .spawn()
+            .expect("cannot start sh");
+        Spinner(child)
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// A config file that does not exist, so the user's own is not read.
+fn no_config() -> PathBuf {
+    std::env::temp_dir().join(format!("rustytop-test-{}-none.toml", std::process::id()))
+}
+
+/// Run rustytop in batch mode with `args`, returning its standard output.
+fn batch(args: &[&str]) -> String {
+    let _running = RUNNING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let output = Command::new(env!("CARGO_BIN_EXE_rustytop"))
+        .arg("-C")
+        .arg(no_config())
+        .arg("-b")
+        .args(args)
+        .stderr(Stdio::inherit())
+        .output()
+        .expect("cannot run rustytop");
+    assert!(output.status.success(), "rustytop failed: {output:?}");
+    String::from_utf8(output.stdout).expect("output is not UTF-8")
+}
+
+/// CPU usage of `pid` in the first snapshot batch mode prints.
+fn first_snapshot_cpu(pid: &str) -> f32 {
+    let csv = batch(&["-p", pid, "-o", "csv"]);
+    let mut lines = csv.lines();
+    let header: Vec<&str> = lines.next().expect("no header").split(',').collect();
+    let row: Vec<&str> = lines
+        .next()
+        .expect("no row for the spinner")
+        .split(',')
+        .collect();
+    let cpu = header
+        .iter()
+        .position(|&name| name == "cpu")
+        .expect("no cpu field");
+
+    assert_eq!(row[0], pid);
+    row[cpu].parse().expect("cpu is not a number")
+}
+
+#[test]
+fn first_snapshot_measures_cpu_usage() {
+    let spinner = Spinner::start();
+    let pid = spinner.0.id().to_string();
+
+    // other load can take the only CPU for the whole first interval, so give
+    // the spinner a few chances; an interval of about 0 fails every time
+    let usages: Vec<f32> = (0..3).map(|_| first_snapshot_cpu(&pid)).collect();
+    assert!(
+        usages.iter().any(|&usage| usage > 10.0),
+        "a spinning process shows {usages:?}% CPU"
+    );
+}
+
+#[test]
+fn prints_one_table_per_iteration() {
+    let tables = batch(&["-n", "2", "-d", "0.5", "-p", "1"]);
+    assert_eq!(tables.matches("PID").count(), 2, "{tables}");
+}